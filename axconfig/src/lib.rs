@@ -3,9 +3,21 @@
 pub const PAGE_SHIFT: usize = 12;
 pub const PAGE_SIZE: usize = 1 << PAGE_SHIFT;
 pub const PHYS_VIRT_OFFSET: usize = 0xffff_ffc0_0000_0000;
-pub const ASPACE_BITS: usize = 39;
+pub const ASPACE_BITS: usize = match option_env!("PAGING_MODE") {
+    Some(s) => parse_paging_mode(s),
+    None => 39,
+}; // sv39, sv48 or sv57, set by `make PAGING_MODE=<mode>`; boot falls back if unsupported
 pub const TASK_STACK_SIZE: usize = 0x40000; // 256 K
+pub const KERNEL_STACK_AREA_BASE: usize = 0xffff_ffff_c000_0000; // in the top root entry in all modes
+pub const KERNEL_STACK_AREA_SIZE: usize = 0x1000_0000; // 256 M, task stacks with guard pages
+pub const USER_STACK_TOP: usize = 0x4_0000_0000;
+pub const USER_STACK_SIZE: usize = 0x100000; // 1 M, backed on demand
 pub const TICKS_PER_SEC: usize = 100;
+pub const WATCHDOG_TICKS: usize = 1000; // 10 s without rescheduling or taking ticks is reported as a lockup
+pub const SMP: usize = match option_env!("SMP") {
+    Some(s) => parse_usize(s),
+    None => 1,
+}; // number of harts, set by `make SMP=<n>`
 
 pub const SIZE_1G: usize = 0x4000_0000;
 pub const SIZE_2M: usize = 0x20_0000;
@@ -42,3 +54,30 @@ pub const fn phys_to_virt(pa: usize) -> usize {
 pub const fn virt_to_phys(va: usize) -> usize {
     va.wrapping_sub(PHYS_VIRT_OFFSET)
 }
+
+const fn parse_usize(s: &str) -> usize {
+    let bytes = s.as_bytes();
+    let mut val = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        assert!(bytes[i].is_ascii_digit(), "invalid number in config");
+        val = val * 10 + (bytes[i] - b'0') as usize;
+        i += 1;
+    }
+    assert!(val > 0, "config number must be positive");
+    val
+}
+
+const fn parse_paging_mode(s: &str) -> usize {
+    let bytes = s.as_bytes();
+    assert!(
+        bytes.len() == 4 && bytes[0] == b's' && bytes[1] == b'v',
+        "PAGING_MODE must be sv39, sv48 or sv57"
+    );
+    let bits = (bytes[2] - b'0') as usize * 10 + (bytes[3] - b'0') as usize;
+    assert!(
+        bits == 39 || bits == 48 || bits == 57,
+        "PAGING_MODE must be sv39, sv48 or sv57"
+    );
+    bits
+}
@@ -1,6 +1,8 @@
 #![no_std]
 #![feature(naked_functions)]
 
+extern crate alloc;
+
 #[cfg(target_arch = "riscv64")]
 mod riscv64;
 #[cfg(target_arch = "riscv64")]
@@ -8,27 +8,55 @@ pub mod cpu;
 pub mod irq;
 pub mod mem;
 pub mod misc;
+pub mod mp;
 pub mod time;
+pub mod tls;
 pub mod trap;
+pub mod uaccess;
 
-pub use context::TaskContext;
+pub use context::{TaskContext, TrapFrame};
+pub use lang_items::PanicHandler;
 pub use misc::terminate;
-pub use paging::write_page_table_root;
+pub use paging::{
+    flush_tlb, flush_tlb_asid, probe_asid_bits, write_page_table_root, write_page_table_root_asid,
+};
+
+unsafe extern "C" {
+    fn trap_vector_base();
+}
 
 unsafe extern "C" fn rust_entry(_hartid: usize, _dtb: usize) {
     unsafe extern "C" {
-        fn trap_vector_base();
         fn rust_main(hartid: usize, dtb: usize);
     }
     unsafe {
+        cpu::init_primary(_hartid);
         trap::set_trap_vector_base(trap_vector_base as usize);
         rust_main(_hartid, _dtb);
     }
 }
 
+unsafe extern "C" fn rust_entry_secondary(hartid: usize) {
+    unsafe extern "C" {
+        fn rust_main_secondary(hartid: usize);
+    }
+    unsafe {
+        cpu::init_secondary(hartid);
+        trap::set_trap_vector_base(trap_vector_base as usize);
+        rust_main_secondary(hartid);
+    }
+}
+
 pub fn platform_init() {
     self::irq::init_percpu();
     self::time::init_percpu();
+    self::trap::init_percpu();
+}
+
+pub fn platform_init_secondary() {
+    self::irq::init_percpu();
+    self::time::init_percpu();
+    self::trap::init_percpu();
 }
 
 struct LogIfImpl;
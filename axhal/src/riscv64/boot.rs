@@ -36,3 +36,28 @@ unsafe extern "C" fn _start() -> ! {
         )
     }
 }
+
+#[unsafe(no_mangle)]
+#[unsafe(link_section = ".text.boot")]
+unsafe extern "C" fn _start_secondary() -> ! {
+    // a0 = hartid
+    // a1 = SP (physical address, passed through `opaque`)
+    unsafe {
+        core::arch::asm!("
+        mv      s0, a0                  // save hartid
+        mv      sp, a1                  // setup boot stack
+        call    {init_mmu}              // enable MMU with the boot page table
+        li      s2, {phys_virt_offset}  // fix up virtual high address
+        add     sp, sp, s2              // readjust stack address
+        mv      a0, s0                  // restore hartid
+        la      a2, {entry}
+        add     a2, a2, s2              // readjust rust_entry_secondary address
+        jalr    a2                      // call rust_entry_secondary(hartid)
+        j       .",
+            init_mmu = sym paging::init_mmu,
+            phys_virt_offset = const axconfig::PHYS_VIRT_OFFSET,
+            entry = sym super::rust_entry_secondary,
+            options(noreturn),
+        )
+    }
+}
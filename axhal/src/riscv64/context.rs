@@ -47,6 +47,41 @@ use core::arch::naked_asm;
      pub sstatus: usize,
  }
 
+impl TrapFrame {
+    /// Creates the initial context of a user program entering at `entry`
+    /// with its stack at `ustack_top`.
+    pub fn new_user(entry: usize, ustack_top: usize) -> Self {
+        const SSTATUS_SIE: usize = 1 << 1;
+        const SSTATUS_SPIE: usize = 1 << 5;
+        const SSTATUS_SPP: usize = 1 << 8;
+        let sstatus = riscv::register::sstatus::read().bits();
+        Self {
+            regs: GeneralRegisters {
+                sp: ustack_top,
+                ..Default::default()
+            },
+            sepc: entry,
+            // Return to U mode with IRQs enabled.
+            sstatus: (sstatus & !(SSTATUS_SPP | SSTATUS_SIE)) | SSTATUS_SPIE,
+        }
+    }
+
+    /// Switches to U mode with this context. Traps from U mode will save
+    /// their frames at the top of the kernel stack `kstack_top`.
+    ///
+    /// # Safety
+    ///
+    /// `kstack_top` must be the top of the current kernel stack, which must
+    /// not hold anything that is needed after this call.
+    pub unsafe fn enter_uspace(&self, kstack_top: usize) -> ! {
+        unsafe extern "C" {
+            fn __enter_uspace(tf: *const TrapFrame, kstack_top: usize) -> !;
+        }
+        crate::irq::disable_irqs();
+        unsafe { __enter_uspace(self, kstack_top) }
+    }
+}
+
 #[repr(C)]
 #[derive(Debug, Default)]
 pub struct TaskContext {
@@ -66,6 +101,8 @@ pub struct TaskContext {
     pub s9: usize,
     pub s10: usize,
     pub s11: usize,
+
+    pub tp: usize, // thread pointer, see `tls`
 }
 
 impl TaskContext {
@@ -73,9 +110,10 @@ impl TaskContext {
         unsafe { core::mem::MaybeUninit::zeroed().assume_init() }
     }
 
-    pub fn init(&mut self, entry: usize, kstack_top: usize) {
+    pub fn init(&mut self, entry: usize, kstack_top: usize, tls_area: usize) {
         self.sp = kstack_top;
         self.ra = entry;
+        self.tp = tls_area;
     }
 
     pub fn switch_to(&mut self, next_ctx: &Self) {
@@ -103,8 +141,10 @@ unsafe extern "C" fn context_switch(_current_task: &mut TaskContext, _next_task:
         sd     s9, 11*8(a0)
         sd     s10, 12*8(a0)
         sd     s11, 13*8(a0)
+        sd     tp, 14*8(a0)
 
         // restore new context
+        ld     tp, 14*8(a1)
         ld     s11, 13*8(a1)
         ld     s10, 12*8(a1)
         ld     s9, 11*8(a1)
@@ -1,13 +1,46 @@
-static mut CURRENT_TASK_PTR: usize = 0;
+percpu::def_percpu! {
+    static CPU_ID: usize = 0;
+    static CURRENT_TASK_PTR: usize = 0;
+}
+
+/// Returns the ID of the current CPU (its hart ID).
+#[inline]
+pub fn this_cpu_id() -> usize {
+    CPU_ID.read_current()
+}
 
 #[inline]
 pub fn current_task_ptr<T>() -> *const T {
-    let _guard = kernel_guard::IrqSave::new();
-    unsafe { CURRENT_TASK_PTR as _ }
+    CURRENT_TASK_PTR.read_current() as _
 }
 
 #[inline]
 pub unsafe fn set_current_task_ptr<T>(ptr: *const T) {
-    let _guard = kernel_guard::IrqSave::new();
-    unsafe { CURRENT_TASK_PTR = ptr as usize }
+    CURRENT_TASK_PTR.write_current(ptr as usize)
+}
+
+pub(super) fn init_primary(hartid: usize) {
+    // The per-CPU areas and arrays are indexed by hart ID, and secondary
+    // harts are only started below `SMP`. Nothing per-CPU works yet to
+    // panic with.
+    if hartid >= axconfig::SMP {
+        crate::ax_println!("Boot hart {} is out of SMP = {}.", hartid, axconfig::SMP);
+        super::misc::terminate();
+    }
+    percpu::init();
+    init_secondary(hartid);
+}
+
+pub(super) fn init_secondary(hartid: usize) {
+    percpu::set_local_thread_pointer(hartid);
+    CPU_ID.write_current(hartid);
+}
+
+struct SpinLockIfImpl;
+
+#[crate_interface::impl_interface]
+impl spinlock::SpinLockIf for SpinLockIfImpl {
+    fn this_cpu_id() -> usize {
+        this_cpu_id()
+    }
 }
@@ -1,11 +1,10 @@
 use axsync::BootOnceCell;
 use handler_table::HandlerTable;
-use riscv::register::{sie, sstatus};
+use riscv::register::{sie, sip, sstatus};
 
 pub const MAX_IRQ_COUNT: usize = 1024;
 pub(super) const INTC_IRQ_BASE: usize = 1 << (usize::BITS - 1);
 
-#[allow(unused)]
 pub(super) const S_SOFT: usize = INTC_IRQ_BASE + 1;
 pub(super) const S_TIMER: usize = INTC_IRQ_BASE + 5;
 pub(super) const S_EXT: usize = INTC_IRQ_BASE + 9;
@@ -27,6 +26,11 @@ pub fn dispatch_irq(scause: usize) {
         S_EXT => {
             crate::irq::dispatch_irq_common(0);
         }
+        S_SOFT => {
+            // IPIs only wake the hart up from `wait_for_irqs`.
+            log::trace!("IRQ: IPI");
+            unsafe { sip::clear_ssoft() }
+        }
         _ => panic!("invalid trap cause: {:#x}", scause),
     }
 }
@@ -68,6 +72,24 @@ pub fn enable_irqs() {
     unsafe { sstatus::set_sie() }
 }
 
+#[inline]
+pub fn disable_irqs() {
+    unsafe { sstatus::clear_sie() }
+}
+
+/// Returns whether IRQs are enabled on the current hart.
+#[inline]
+pub fn irqs_enabled() -> bool {
+    sstatus::read().sie()
+}
+
+/// Stalls the hart until an interrupt is pending, even if IRQs are
+/// disabled.
+#[inline]
+pub fn wait_for_irqs() {
+    riscv::asm::wfi()
+}
+
 pub(super) fn init_percpu() {
     unsafe {
         sie::set_ssoft();
@@ -1,8 +1,24 @@
 use axlog::error;
 use core::panic::PanicInfo;
+use crate_interface::{call_interface, def_interface};
+
+/// Panic handler interface.
+///
+/// This trait is defined with the [`#[def_interface]`][1] attribute. Users
+/// should implement it with [`#[impl_interface]`][2] in any other crate.
+///
+/// [1]: crate_interface::def_interface
+/// [2]: crate_interface::impl_interface
+#[def_interface]
+pub trait PanicHandler {
+    /// Contains the panic to the current task and never returns if it can.
+    /// The machine is terminated once it returns.
+    fn handle_panic(info: &PanicInfo);
+}
 
 #[panic_handler]
-fn panic(_info: &PanicInfo) -> ! {
-    error!("{}", _info);
+fn panic(info: &PanicInfo) -> ! {
+    error!("{}", info);
+    call_interface!(PanicHandler::handle_panic, info);
     super::misc::terminate()
 }
@@ -1,11 +1,11 @@
 use axconfig::{PAGE_SIZE, align_down, align_up, virt_to_phys};
-use page_table::{PAGE_KERNEL_RO, PAGE_KERNEL_RW, PAGE_KERNEL_RX};
+use page_table::MappingFlags;
 
 #[derive(Debug)]
 pub struct MemRegion {
     pub paddr: usize,
     pub size: usize,
-    pub flags: usize,
+    pub flags: MappingFlags,
     pub name: &'static str,
 }
 
@@ -14,37 +14,37 @@ pub fn kernel_image_regions() -> impl Iterator<Item = MemRegion> {
         MemRegion {
             paddr: virt_to_phys(_stext as usize),
             size: _etext as usize - _stext as usize,
-            flags: PAGE_KERNEL_RX,
+            flags: MappingFlags::KERNEL_RX,
             name: ".text",
         },
         MemRegion {
             paddr: virt_to_phys(_srodata as usize),
             size: _erodata as usize - _srodata as usize,
-            flags: PAGE_KERNEL_RO,
+            flags: MappingFlags::READ,
             name: ".rodata",
         },
         MemRegion {
             paddr: virt_to_phys(_sdata as usize),
             size: _edata as usize - _sdata as usize,
-            flags: PAGE_KERNEL_RW,
+            flags: MappingFlags::KERNEL_RW,
             name: ".data .tdata .tbss .percpu",
         },
         MemRegion {
             paddr: virt_to_phys(_skernel as usize) - 0x100000,
             size: 0x100000,
-            flags: PAGE_KERNEL_RW,
+            flags: MappingFlags::KERNEL_RW,
             name: "early heap",
         },
         MemRegion {
             paddr: virt_to_phys(boot_stack as usize),
             size: boot_stack_top as usize - boot_stack as usize,
-            flags: PAGE_KERNEL_RW,
+            flags: MappingFlags::KERNEL_RW,
             name: "boot stack",
         },
         MemRegion {
             paddr: virt_to_phys(_sbss as usize),
             size: _ebss as usize - _sbss as usize,
-            flags: PAGE_KERNEL_RW,
+            flags: MappingFlags::KERNEL_RW,
             name: ".bss",
         },
     ]
@@ -72,7 +72,7 @@ pub fn free_regions(phys_mem_size: usize) -> impl Iterator<Item = MemRegion> {
     core::iter::once(MemRegion {
         paddr: start,
         size: align_down(size, PAGE_SIZE),
-        flags: PAGE_KERNEL_RW,
+        flags: MappingFlags::KERNEL_RW,
         name: "free memory",
     })
 }
@@ -0,0 +1,23 @@
+use axconfig::virt_to_phys;
+
+/// Starts the given secondary hart through the SBI HSM extension.
+///
+/// The hart begins executing `_start_secondary` with MMU off, using
+/// `stack_top` (a physical address) as its boot stack. Returns the SBI
+/// error if it could not be started.
+pub fn start_secondary_cpu(hartid: usize, stack_top: usize) -> Result<(), sbi_rt::SbiRet> {
+    unsafe extern "C" {
+        fn _start_secondary();
+    }
+    let entry = virt_to_phys(_start_secondary as usize);
+    let ret = sbi_rt::hart_start(hartid, entry, stack_top);
+    if ret.is_err() { Err(ret) } else { Ok(()) }
+}
+
+/// Sends an IPI to the given hart, waking it up from `wait_for_irqs`.
+pub fn send_ipi(hartid: usize) {
+    let ret = sbi_rt::send_ipi(sbi_rt::HartMask::from_mask_base(1, hartid));
+    if ret.is_err() {
+        log::warn!("failed to send IPI to CPU {}: {:?}", hartid, ret);
+    }
+}
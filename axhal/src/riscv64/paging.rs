@@ -1,23 +1,87 @@
-use axconfig::{SIZE_1G, phys_pfn};
-use page_table::{PAGE_KERNEL_RWX, PageTable};
-use riscv::register::satp;
+use axconfig::{ASPACE_BITS, PAGE_SIZE, SIZE_1G, phys_pfn};
+use page_table::{MappingFlags, PageTable};
+
+/// Pages reserved for the boot page table in `linker.lds`: the root and up
+/// to two intermediate tables for each of the two boot mappings.
+const BOOT_PT_PAGES: usize = 5;
 
 unsafe extern "C" {
     unsafe fn boot_page_table();
 }
 
+/// Builds the boot page table for the configured paging mode, falling back
+/// to smaller modes the hart does not support.
+///
+/// Runs with the MMU off, so intermediate tables are linked by the
+/// addresses they are running at.
 pub unsafe fn init_boot_page_table() {
-    let mut pt: PageTable = PageTable::init(boot_page_table as usize, 0);
-    let _ = pt.map(0x8000_0000, 0x8000_0000, SIZE_1G, SIZE_1G, PAGE_KERNEL_RWX);
-    let _ = pt.map(
-        0xffff_ffc0_8000_0000,
-        0x8000_0000,
-        SIZE_1G,
-        SIZE_1G,
-        PAGE_KERNEL_RWX,
-    );
+    let root = boot_page_table as usize;
+    let mut bits = ASPACE_BITS;
+    loop {
+        page_table::set_aspace_bits(bits);
+        unsafe { core::ptr::write_bytes(root as *mut u8, 0, BOOT_PT_PAGES * PAGE_SIZE) };
+        let mut next_page = root + PAGE_SIZE;
+        map_boot_1g(root, &mut next_page, 0x8000_0000, 0x8000_0000);
+        map_boot_1g(root, &mut next_page, 0xffff_ffc0_8000_0000, 0x8000_0000);
+        // Sv39 is always there if paging is.
+        if bits == 39 || unsafe { probe_paging_mode(root) } {
+            break;
+        }
+        bits -= 9;
+    }
+}
+
+/// Maps 1G at `va` to `pa` with a huge page, taking the intermediate tables
+/// from `next_page`.
+fn map_boot_1g(root: usize, next_page: &mut usize, va: usize, pa: usize) {
+    let mut table = root;
+    let mut level = 0;
+    loop {
+        let mut pt: PageTable = PageTable::init(table, level);
+        if pt.entry_size() == SIZE_1G {
+            let _ = pt.map(va, pa, SIZE_1G, SIZE_1G, MappingFlags::KERNEL_RWX);
+            return;
+        }
+        table = *next_page;
+        *next_page += PAGE_SIZE;
+        pt.link_table(pt.entry_index(va), table);
+        level += 1;
+    }
 }
 
+/// Returns whether the hart accepts the current paging mode in `satp`.
+///
+/// `satp` is WARL: writing an unsupported mode has no effect. `root` must
+/// identity-map the running code, as translation is on for a moment.
+unsafe fn probe_paging_mode(root: usize) -> bool {
+    let satp = satp_bits(root, 0);
+    let read: usize;
+    unsafe {
+        core::arch::asm!(
+            "csrw satp, {satp}",
+            "sfence.vma",
+            "csrr {read}, satp",
+            "csrw satp, zero",
+            "sfence.vma",
+            satp = in(reg) satp,
+            read = out(reg) read,
+        );
+    }
+    read == satp
+}
+
+/// Returns the `satp` value selecting the current paging mode, `asid` and
+/// `root_pa`.
+#[inline]
+fn satp_bits(root_pa: usize, asid: usize) -> usize {
+    // Sv39 = 8, Sv48 = 9, Sv57 = 10
+    let mode = 8 + (page_table::aspace_bits() - 39) / 9;
+    (mode << 60) | ((asid & SATP_ASID_MASK) << SATP_ASID_SHIFT) | phys_pfn(root_pa)
+}
+
+const SATP_ASID_SHIFT: usize = 44;
+const SATP_ASID_MASK: usize = 0xffff;
+
 pub unsafe fn init_mmu() {
     unsafe {
         write_page_table_root(boot_page_table as usize);
@@ -33,7 +97,55 @@ pub unsafe fn init_mmu() {
 /// memory access violations and system crashes.
 pub unsafe fn write_page_table_root(pa: usize) {
     unsafe {
-        satp::set(satp::Mode::Sv39, 0, phys_pfn(pa));
+        core::arch::asm!("csrw satp, {}", in(reg) satp_bits(pa, 0));
         riscv::asm::sfence_vma_all();
     }
 }
+
+/// Switches to the page table at `pa` tagged with `asid`, without flushing
+/// the TLB.
+///
+/// # Safety
+///
+/// Same as [`write_page_table_root`], and the caller must ensure the TLB
+/// holds no stale entries of `asid`.
+pub unsafe fn write_page_table_root_asid(pa: usize, asid: usize) {
+    unsafe { core::arch::asm!("csrw satp, {}", in(reg) satp_bits(pa, asid)) };
+}
+
+/// Returns the number of ASID bits the hart implements.
+///
+/// `satp.ASID` is WARL: the unimplemented bits read back as zero.
+pub fn probe_asid_bits() -> usize {
+    let asid: usize;
+    unsafe {
+        core::arch::asm!(
+            "csrr {old}, satp",
+            "or {tmp}, {old}, {mask}",
+            "csrw satp, {tmp}",
+            "csrr {tmp}, satp",
+            "csrw satp, {old}",
+            "sfence.vma",
+            old = out(reg) _,
+            tmp = out(reg) asid,
+            mask = in(reg) SATP_ASID_MASK << SATP_ASID_SHIFT,
+        );
+    }
+    ((asid >> SATP_ASID_SHIFT) & SATP_ASID_MASK).count_ones() as usize
+}
+
+/// Flushes the TLB entries of `vaddr` in every address space, global ones
+/// included, or the whole TLB if `None`.
+#[inline]
+pub fn flush_tlb(vaddr: Option<usize>) {
+    match vaddr {
+        Some(vaddr) => unsafe { core::arch::asm!("sfence.vma {}, zero", in(reg) vaddr) },
+        None => riscv::asm::sfence_vma_all(),
+    }
+}
+
+/// Flushes the non-global TLB entries tagged with `asid`.
+#[inline]
+pub fn flush_tlb_asid(asid: usize) {
+    unsafe { core::arch::asm!("sfence.vma zero, {}", in(reg) asid) };
+}
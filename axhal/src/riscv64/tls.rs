@@ -0,0 +1,71 @@
+//! Thread-local storage.
+//!
+//! Every task gets a copy of the `.tdata` and `.tbss` image, and `tp` points
+//! to it while the task runs. Thread-local variables are reached at fixed
+//! offsets from `tp` (the local-exec model, RISC-V has no TCB before them).
+
+use core::alloc::Layout;
+use core::ptr::NonNull;
+
+/// Alignment of the TLS areas, which must cover that of the TLS segment.
+const TLS_ALIGN: usize = 64;
+
+unsafe extern "C" {
+    fn _stdata();
+    fn _etdata();
+    fn _etbss();
+}
+
+/// A thread-local storage area initialized from the TLS image.
+pub struct TlsArea {
+    base: NonNull<u8>,
+    layout: Layout,
+}
+
+impl TlsArea {
+    /// Allocates a TLS area with the initial values of the thread-local
+    /// variables.
+    pub fn alloc() -> Self {
+        let size = _etbss as usize - _stdata as usize;
+        let layout = Layout::from_size_align(size.max(TLS_ALIGN), TLS_ALIGN).unwrap();
+        let base = unsafe { alloc::alloc::alloc_zeroed(layout) };
+        let Some(base) = NonNull::new(base) else {
+            alloc::alloc::handle_alloc_error(layout);
+        };
+        let tdata_size = _etdata as usize - _stdata as usize;
+        unsafe {
+            core::ptr::copy_nonoverlapping(_stdata as *const u8, base.as_ptr(), tdata_size);
+        }
+        Self { base, layout }
+    }
+
+    /// Returns the value of `tp` for this area.
+    pub fn tls_ptr(&self) -> usize {
+        self.base.as_ptr() as usize
+    }
+}
+
+impl Drop for TlsArea {
+    fn drop(&mut self) {
+        unsafe { alloc::alloc::dealloc(self.base.as_ptr(), self.layout) }
+    }
+}
+
+/// Reads the thread pointer (`tp`) of the current CPU.
+#[inline]
+pub fn read_thread_pointer() -> usize {
+    let tp;
+    unsafe { core::arch::asm!("mv {}, tp", out(reg) tp) };
+    tp
+}
+
+/// Writes the thread pointer (`tp`) of the current CPU.
+///
+/// # Safety
+///
+/// `tp` must point to a [`TlsArea`] that outlives its use, as all
+/// thread-local variables are accessed through it.
+#[inline]
+pub unsafe fn write_thread_pointer(tp: usize) {
+    unsafe { core::arch::asm!("mv tp, {}", in(reg) tp) };
+}
@@ -1,13 +1,50 @@
 use super::context::TrapFrame;
+use axconfig::SMP;
 use axlog::debug;
+use core::mem::{offset_of, size_of};
 use crate_interface::{call_interface, def_interface};
+use page_table::AccessType;
 use riscv::register::scause::{self, Trap};
 
 core::arch::global_asm!(
     include_str!("trap.S"),
-    trapframe_size = const core::mem::size_of::<TrapFrame>(),
+    trapframe_size = const size_of::<TrapFrame>(),
+    percpu_stack_limit = const offset_of!(TrapPercpu, stack_limit),
+    percpu_overflow_stack_top = const offset_of!(TrapPercpu, overflow_stack_top),
+    percpu_scratch = const offset_of!(TrapPercpu, scratch),
 );
 
+const OVERFLOW_STACK_SIZE: usize = 0x4000; // 16 K
+
+/// Per-CPU state of the S-mode trap entry.
+#[repr(C)]
+struct TrapPercpu {
+    /// Traps with `sp` below it are stack overflows, `0` if unchecked.
+    stack_limit: usize,
+    /// Top of the stack that stack overflows are reported on.
+    overflow_stack_top: usize,
+    /// Saves `t0` while checking the stack.
+    scratch: usize,
+}
+
+/// Placed first in the per-CPU area (see `linker.lds`), so `trap.S` finds
+/// it at `gp` without spare registers.
+static TRAP_PERCPU: percpu::PerCpu<TrapPercpu> = {
+    #[unsafe(link_section = ".percpu.trap")]
+    static mut TEMPLATE: TrapPercpu = TrapPercpu {
+        stack_limit: 0,
+        overflow_stack_top: 0,
+        scratch: 0,
+    };
+    percpu::PerCpu::new(&raw mut TEMPLATE)
+};
+
+#[repr(align(16))]
+struct OverflowStack([u8; OVERFLOW_STACK_SIZE]);
+
+static mut OVERFLOW_STACKS: [OverflowStack; SMP] =
+    [const { OverflowStack([0; OVERFLOW_STACK_SIZE]) }; SMP];
+
 /// Writes Supervisor Trap Vector Base Address Register (`stvec`).
 #[inline]
 pub fn set_trap_vector_base(addr: usize) {
@@ -18,29 +55,107 @@ pub fn set_trap_vector_base(addr: usize) {
     }
 }
 
+/// Sets up the stack overflow stack of the current CPU.
+pub fn init_percpu() {
+    assert_eq!(TRAP_PERCPU.offset(), 0);
+    let cpu_id = super::cpu::this_cpu_id();
+    let stacks = &raw const OVERFLOW_STACKS;
+    let stack = unsafe { (*stacks)[cpu_id].0.as_ptr() } as usize;
+    TRAP_PERCPU.with_current(|t| t.overflow_stack_top = stack + OVERFLOW_STACK_SIZE);
+}
+
+/// Sets the bottom of the kernel stack the current CPU runs on, `0` if it
+/// has no guard page. Traps pushing their frame below it are reported as
+/// stack overflows.
+pub fn set_kernel_stack_bottom(bottom: usize) {
+    let limit = match bottom {
+        0 => 0,
+        bottom => bottom + size_of::<TrapFrame>(),
+    };
+    TRAP_PERCPU.with_current(|t| t.stack_limit = limit);
+}
+
 #[unsafe(no_mangle)]
-fn riscv_trap_handler(tf: &mut TrapFrame) {
+fn riscv_trap_handler(tf: &mut TrapFrame, from_user: bool) {
     let scause = scause::read();
     match scause.cause() {
         // Use usize constants for Exception codes since we don't have the Exception enum
         Trap::Exception(3) => handle_breakpoint(&mut tf.sepc), // 3 is the standard code for Breakpoint exception
+        Trap::Exception(8) if from_user => handle_syscall(tf), // 8 is Environment call from U-mode
+        // 12, 13 and 15 are Instruction, Load and Store/AMO page faults
+        Trap::Exception(12) => handle_page_fault(tf, AccessType::Execute, from_user),
+        Trap::Exception(13) => handle_page_fault(tf, AccessType::Read, from_user),
+        Trap::Exception(15) => handle_page_fault(tf, AccessType::Write, from_user),
         Trap::Interrupt(_) => handle_irq_extern(scause.bits()),
         _ => {
             panic!(
-                "Unhandled trap {:?} @ {:#x}:\n{:#x?}",
+                "Unhandled trap {:?} @ {:#x} (from {} mode):\n{:#x?}",
                 scause.cause(),
                 tf.sepc,
+                if from_user { "U" } else { "S" },
                 tf
             );
         }
     }
 }
 
+/// Called on the overflow stack when a trap from S mode finds its stack
+/// exhausted, e.g. on a fault on the guard page.
+#[unsafe(no_mangle)]
+fn riscv_stack_overflow(tf: &TrapFrame) -> ! {
+    TRAP_PERCPU.with_current(|t| t.stack_limit = 0);
+    let scause = scause::read();
+    if let Trap::Exception(12 | 13 | 15) = scause.cause() {
+        let vaddr = riscv::register::stval::read();
+        // Lets the kernel name the task before panicking.
+        call_interface!(
+            TrapHandler::handle_page_fault,
+            vaddr,
+            AccessType::Write,
+            false
+        );
+    }
+    panic!(
+        "Kernel stack overflow, sp={:#x}, trap {:?} @ {:#x}:\n{:#x?}",
+        tf.regs.sp,
+        scause.cause(),
+        tf.sepc,
+        tf
+    );
+}
+
 fn handle_breakpoint(sepc: &mut usize) {
     debug!("Exception(Breakpoint) @ {:#x} ", sepc);
     *sepc += 2
 }
 
+fn handle_page_fault(tf: &TrapFrame, access: AccessType, is_user: bool) {
+    let vaddr = riscv::register::stval::read();
+    if !call_interface!(TrapHandler::handle_page_fault, vaddr, access, is_user) {
+        panic!(
+            "Unhandled {} page fault @ {:#x}, vaddr={:#x}, access={:?}:\n{:#x?}",
+            if is_user { "user" } else { "kernel" },
+            tf.sepc,
+            vaddr,
+            access,
+            tf
+        );
+    }
+}
+
+fn handle_syscall(tf: &mut TrapFrame) {
+    // Skip the `ecall` instruction.
+    tf.sepc += 4;
+    let args = [
+        tf.regs.a0, tf.regs.a1, tf.regs.a2, tf.regs.a3, tf.regs.a4, tf.regs.a5,
+    ];
+    // Serve the system call with IRQs enabled, like a normal kernel path.
+    crate::irq::enable_irqs();
+    let ret = call_interface!(TrapHandler::handle_syscall, tf.regs.a7, args);
+    crate::irq::disable_irqs();
+    tf.regs.a0 = ret as usize;
+}
+
 /// Trap handler interface.
 ///
 /// This trait is defined with the [`#[def_interface]`][1] attribute. Users
@@ -52,7 +167,13 @@ fn handle_breakpoint(sepc: &mut usize) {
 pub trait TrapHandler {
     /// Handles interrupt requests for the given IRQ number.
     fn handle_irq(irq_num: usize);
-    // more e.g.: handle_page_fault();
+    /// Handles a system call from user space, returning the value for `a0`.
+    fn handle_syscall(syscall_num: usize, args: [usize; 6]) -> isize;
+    /// Handles a page fault at `vaddr`, returning whether it was resolved.
+    ///
+    /// A fault from user space that cannot be resolved should terminate the
+    /// faulting task instead of returning.
+    fn handle_page_fault(vaddr: usize, access: AccessType, is_user: bool) -> bool;
 }
 
 /// Call the external IRQ handler.
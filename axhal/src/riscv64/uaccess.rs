@@ -0,0 +1,25 @@
+//! Supervisor access to user memory.
+
+use riscv::register::sstatus;
+
+/// Clears `sstatus.SUM` on drop, even if the access panics.
+struct SumGuard;
+
+impl Drop for SumGuard {
+    fn drop(&mut self) {
+        unsafe { sstatus::clear_sum() };
+    }
+}
+
+/// Runs `f` with S-mode access to user pages (`sstatus.SUM`) enabled.
+/// Anywhere else, a kernel access to user memory faults.
+///
+/// IRQs stay disabled meanwhile, as the flag belongs to the CPU and must
+/// not leak into the task a preemption would switch to. Calls do not nest,
+/// and `f` must not block.
+pub fn with_user_access<R>(f: impl FnOnce() -> R) -> R {
+    let _irq = kernel_guard::IrqSave::new();
+    unsafe { sstatus::set_sum() };
+    let _sum = SumGuard;
+    f()
+}
@@ -0,0 +1,133 @@
+//! ASID allocation and address space switching.
+//!
+//! ASIDs are handed out in generations: once they run out, the generation
+//! is bumped, every CPU flushes its whole TLB before it runs an address
+//! space of the new generation, and address spaces holding an ASID of an
+//! older generation get a fresh one on their next switch. ASID 0 is kept
+//! for the kernel page table, whose mappings are global.
+
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use spinlock::SpinNoIrq;
+
+/// Bits of an ASID tagged with its generation that hold the ASID.
+const ASID_SHIFT: u32 = 16;
+const ASID_MASK: u64 = (1 << ASID_SHIFT) - 1;
+
+const NO_CPU: usize = usize::MAX;
+
+static ASID_ALLOCATOR: SpinNoIrq<AsidAllocator> = SpinNoIrq::new(AsidAllocator::new());
+
+percpu::def_percpu! {
+    /// The ASID generation the TLB of the CPU was last flushed for.
+    static CPU_GENERATION: u64 = 0;
+}
+
+struct AsidAllocator {
+    /// Number of ASID bits implemented by the harts, `0` if none.
+    bits: usize,
+    generation: u64,
+    next: u64,
+}
+
+impl AsidAllocator {
+    const fn new() -> Self {
+        Self {
+            bits: 0,
+            generation: 1,
+            next: 1,
+        }
+    }
+
+    /// Returns the ASID of `tagged` if it is still of the current
+    /// generation, or allocates a new one, starting a new generation when
+    /// they run out.
+    fn refresh(&mut self, tagged: &AtomicU64) -> u64 {
+        let cur = tagged.load(Ordering::Relaxed);
+        if cur >> ASID_SHIFT == self.generation {
+            return cur & ASID_MASK;
+        }
+        if self.next >> self.bits != 0 {
+            self.generation += 1;
+            self.next = 1;
+        }
+        let asid = self.next;
+        self.next += 1;
+        tagged.store((self.generation << ASID_SHIFT) | asid, Ordering::Relaxed);
+        asid
+    }
+}
+
+/// Probes the ASID bits of the hart. Must run before any user address
+/// space is switched to.
+pub(crate) fn init() {
+    let bits = axhal::probe_asid_bits();
+    ASID_ALLOCATOR.lock().bits = bits;
+    log::info!("ASID bits: {}", bits);
+}
+
+/// The hardware context of a user address space: its page table root and
+/// ASID.
+pub struct MmContext {
+    root: usize,
+    /// The ASID tagged with its generation, `0` if never allocated.
+    asid: AtomicU64,
+    /// The CPU this context last ran on.
+    last_cpu: AtomicUsize,
+}
+
+impl MmContext {
+    pub(crate) const fn new(root: usize) -> Self {
+        Self {
+            root,
+            asid: AtomicU64::new(0),
+            last_cpu: AtomicUsize::new(NO_CPU),
+        }
+    }
+
+    /// Returns the physical address of the root page table.
+    pub fn page_table_root(&self) -> usize {
+        self.root
+    }
+}
+
+/// Switches the current CPU to the address space `next`, or to the kernel
+/// page table if `None`.
+///
+/// Flushes only what may be stale: the whole TLB after an ASID rollover,
+/// or the ASID of `next` if it last ran on another CPU, whose updates were
+/// not flushed here.
+///
+/// # Safety
+///
+/// Must be called with IRQs disabled, and the page table of `next` must
+/// stay alive while the CPU runs on it.
+pub unsafe fn switch_to(next: Option<&MmContext>) {
+    let mut allocator = ASID_ALLOCATOR.lock();
+    let Some(ctx) = next else {
+        if allocator.bits == 0 {
+            unsafe { axhal::write_page_table_root(crate::kernel_page_table_root()) };
+        } else {
+            // Kernel mappings are global, and user entries tagged with other
+            // ASIDs cannot be hit.
+            unsafe { axhal::write_page_table_root_asid(crate::kernel_page_table_root(), 0) };
+        }
+        return;
+    };
+
+    let cpu_id = axhal::cpu::this_cpu_id();
+    let last_cpu = ctx.last_cpu.swap(cpu_id, Ordering::Relaxed);
+    if allocator.bits == 0 {
+        // Every space shares ASID 0.
+        unsafe { axhal::write_page_table_root(ctx.root) };
+        return;
+    }
+
+    let asid = allocator.refresh(&ctx.asid) as usize;
+    unsafe { axhal::write_page_table_root_asid(ctx.root, asid) };
+    if CPU_GENERATION.read_current() != allocator.generation {
+        CPU_GENERATION.write_current(allocator.generation);
+        axhal::flush_tlb(None);
+    } else if last_cpu != cpu_id {
+        axhal::flush_tlb_asid(asid);
+    }
+}
@@ -0,0 +1,217 @@
+use crate::{MmContext, MmError, MmResult, kernel_page_table_root, user_space_end};
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use axconfig::{PAGE_SIZE, align_down, align_up, is_aligned};
+use axconfig::{phys_to_virt, virt_to_phys};
+use core::alloc::Layout;
+use core::ops::Range;
+use page_table::{AccessType, ENTRIES_COUNT, MappingFlags, OwnedPageTable, PageTable};
+
+/// A user address space.
+///
+/// The upper half of the root table is shared with the kernel page table,
+/// the lower half maps user pages backed by frames owned by this space.
+/// The page tables of the lower half are freed along with the space.
+pub struct AddrSpace {
+    pt: OwnedPageTable,
+    /// Page-aligned user address -> (backing frame, mapping flags).
+    frames: BTreeMap<usize, (usize, MappingFlags)>,
+    /// Areas backed on first touch, with their mapping flags.
+    lazy_areas: Vec<(Range<usize>, MappingFlags)>,
+    ctx: Arc<MmContext>,
+}
+
+impl AddrSpace {
+    /// Creates an empty user address space.
+    pub fn new_user() -> Self {
+        let mut pt = OwnedPageTable::new();
+        let kernel_pt = PageTable::init(phys_to_virt(kernel_page_table_root()), 0);
+        pt.share(&kernel_pt, ENTRIES_COUNT / 2..ENTRIES_COUNT);
+        let ctx = Arc::new(MmContext::new(pt.root_paddr()));
+        Self {
+            pt,
+            frames: BTreeMap::new(),
+            lazy_areas: Vec::new(),
+            ctx,
+        }
+    }
+
+    /// Returns the physical address of the root page table.
+    pub fn page_table_root(&self) -> usize {
+        self.pt.root_paddr()
+    }
+
+    /// Returns the context to switch to this space with.
+    pub fn context(&self) -> &Arc<MmContext> {
+        &self.ctx
+    }
+
+    /// Maps `[start, start + size)` to newly allocated zeroed frames.
+    ///
+    /// Pages that are already mapped keep their frame and get the union of
+    /// the old and new flags, so adjacent segments may share a page.
+    pub fn map_alloc(&mut self, start: usize, size: usize, flags: MappingFlags) -> MmResult {
+        let end = align_up(
+            start.checked_add(size).ok_or(MmError::InvalidParam)?,
+            PAGE_SIZE,
+        );
+        if end > user_space_end() {
+            return Err(MmError::InvalidParam);
+        }
+        for va in (align_down(start, PAGE_SIZE)..end).step_by(PAGE_SIZE) {
+            match self.frames.get_mut(&va) {
+                Some((_, old)) => {
+                    *old |= flags;
+                    self.pt.protect(va, PAGE_SIZE, *old)?;
+                }
+                None => {
+                    let frame = alloc_frame();
+                    if let Err(err) =
+                        self.pt
+                            .map(va, virt_to_phys(frame), PAGE_SIZE, PAGE_SIZE, flags)
+                    {
+                        dealloc_frame(frame);
+                        return Err(err.into());
+                    }
+                    self.frames.insert(va, (frame, flags));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reserves `[start, start + size)` to be backed by zeroed frames when
+    /// first touched.
+    pub fn map_lazy(&mut self, start: usize, size: usize, flags: MappingFlags) -> MmResult {
+        let end = align_up(
+            start.checked_add(size).ok_or(MmError::InvalidParam)?,
+            PAGE_SIZE,
+        );
+        let start = align_down(start, PAGE_SIZE);
+        if end > user_space_end() {
+            return Err(MmError::InvalidParam);
+        }
+        self.pt.map_lazy(start, end - start, flags)?;
+        self.lazy_areas.push((start..end, flags));
+        Ok(())
+    }
+
+    /// Unmaps `[start, start + size)`, which must be fully mapped, and frees
+    /// its frames along with the page tables left empty.
+    pub fn unmap(&mut self, start: usize, size: usize) -> MmResult {
+        if !is_aligned(start, PAGE_SIZE) || !is_aligned(size, PAGE_SIZE) {
+            return Err(MmError::InvalidParam);
+        }
+        if !self.check_region(start, size, MappingFlags::empty()) {
+            return Err(MmError::NotMapped);
+        }
+        let end = start + size;
+        self.pt.unmap(start, size)?;
+
+        let pages: Vec<usize> = self.frames.range(start..end).map(|(&va, _)| va).collect();
+        for va in pages {
+            let (frame, _) = self.frames.remove(&va).unwrap();
+            dealloc_frame(frame);
+        }
+        let mut lazy_areas = Vec::new();
+        for (area, flags) in self.lazy_areas.drain(..) {
+            if area.end <= start || area.start >= end {
+                lazy_areas.push((area, flags));
+                continue;
+            }
+            if area.start < start {
+                lazy_areas.push((area.start..start, flags));
+            }
+            if area.end > end {
+                lazy_areas.push((end..area.end, flags));
+            }
+        }
+        self.lazy_areas = lazy_areas;
+        Ok(())
+    }
+
+    /// Resolves a page fault at `vaddr` by backing a lazily mapped page.
+    /// Returns whether the fault was resolved.
+    pub fn handle_page_fault(&mut self, vaddr: usize, access: AccessType) -> bool {
+        let Some(flags) = self.lazy_flags(vaddr) else {
+            return false;
+        };
+        match self.pt.populate_lazy(vaddr, access) {
+            Some(frame) => {
+                let page = align_down(vaddr, PAGE_SIZE);
+                self.frames.insert(page, (phys_to_virt(frame), flags));
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn lazy_flags(&self, vaddr: usize) -> Option<MappingFlags> {
+        self.lazy_areas
+            .iter()
+            .find(|(area, _)| area.contains(&vaddr))
+            .map(|&(_, flags)| flags)
+    }
+
+    /// Copies `data` into the mapped user memory at `start`.
+    pub fn write(&mut self, start: usize, data: &[u8]) -> MmResult {
+        let mut va = start;
+        let mut data = data;
+        while !data.is_empty() {
+            let page = align_down(va, PAGE_SIZE);
+            let &(frame, _) = self.frames.get(&page).ok_or(MmError::NotMapped)?;
+            let offset = va - page;
+            let n = data.len().min(PAGE_SIZE - offset);
+            unsafe {
+                core::ptr::copy_nonoverlapping(data.as_ptr(), (frame + offset) as *mut u8, n);
+            }
+            va += n;
+            data = &data[n..];
+        }
+        Ok(())
+    }
+
+    /// Returns whether `[start, start + size)` is mapped with at least `flags`.
+    pub fn check_region(&self, start: usize, size: usize, flags: MappingFlags) -> bool {
+        let Some(end) = start.checked_add(size) else {
+            return false;
+        };
+        if end > user_space_end() {
+            return false;
+        }
+        (align_down(start, PAGE_SIZE)..align_up(end, PAGE_SIZE))
+            .step_by(PAGE_SIZE)
+            .all(|va| {
+                let f = match self.frames.get(&va) {
+                    Some(&(_, f)) => Some(f),
+                    None => self.lazy_flags(va),
+                };
+                matches!(f, Some(f) if f.contains(flags))
+            })
+    }
+}
+
+impl Drop for AddrSpace {
+    fn drop(&mut self) {
+        for &(frame, _) in self.frames.values() {
+            dealloc_frame(frame);
+        }
+    }
+}
+
+fn frame_layout() -> Layout {
+    Layout::from_size_align(PAGE_SIZE, PAGE_SIZE).unwrap()
+}
+
+fn alloc_frame() -> usize {
+    let ptr = unsafe { alloc::alloc::alloc_zeroed(frame_layout()) };
+    if ptr.is_null() {
+        alloc::alloc::handle_alloc_error(frame_layout());
+    }
+    ptr as usize
+}
+
+fn dealloc_frame(frame: usize) {
+    unsafe { alloc::alloc::dealloc(frame as *mut u8, frame_layout()) }
+}
@@ -0,0 +1,120 @@
+//! Kernel stacks mapped in the stack area of the kernel page table, each
+//! with an unmapped guard page below it.
+
+use crate::kernel_page_table_root;
+use alloc::vec::Vec;
+use axconfig::{KERNEL_STACK_AREA_BASE, KERNEL_STACK_AREA_SIZE, PAGE_SIZE};
+use axconfig::{phys_to_virt, virt_to_phys};
+use core::alloc::Layout;
+use page_table::{MappingFlags, PageTable};
+use spinlock::SpinNoIrq;
+
+/// Fills the unused part of the stacks, to find how deep they were used.
+const STACK_PATTERN: u64 = 0xdead_beef_dead_beef;
+
+static STACK_AREA: SpinNoIrq<StackArea> = SpinNoIrq::new(StackArea {
+    next: KERNEL_STACK_AREA_BASE,
+    free: Vec::new(),
+});
+
+/// The stack area. Slots are never unmapped: other CPUs may still cache the
+/// translations of a freed stack (there is no TLB shootdown), so freed
+/// stacks keep their frames and are handed out again as they are. Sizes are
+/// rounded up to powers of two, so that a freed slot fits the later stacks
+/// of its size class, whatever size each thread asks for.
+struct StackArea {
+    /// Start of the unused part of the area.
+    next: usize,
+    /// Bottoms and sizes of the freed stacks.
+    free: Vec<(usize, usize)>,
+}
+
+/// Links the table of the stack area into the kernel page table root, so
+/// that user address spaces sharing the root entries see the stacks mapped
+/// later.
+pub(crate) fn init(kernel_pt: &mut PageTable) {
+    let index = kernel_pt.entry_index(KERNEL_STACK_AREA_BASE);
+    if kernel_pt.entry_at(index).is_unused() {
+        kernel_pt.link_table(index, PageTable::alloc_table(1).root_paddr());
+    }
+}
+
+/// A kernel stack with an unmapped guard page below it.
+pub struct KernelStack {
+    bottom: usize,
+    size: usize,
+}
+
+impl KernelStack {
+    /// Allocates a stack of at least `size` bytes from page frames.
+    pub fn alloc(size: usize) -> Self {
+        let size = size.max(PAGE_SIZE).next_power_of_two();
+        let mut area = STACK_AREA.lock();
+        if let Some(i) = area.free.iter().position(|&(_, s)| s == size) {
+            let (bottom, size) = area.free.swap_remove(i);
+            drop(area);
+            return Self { bottom, size }.filled();
+        }
+
+        let bottom = area.next + PAGE_SIZE;
+        assert!(
+            bottom + size <= KERNEL_STACK_AREA_BASE + KERNEL_STACK_AREA_SIZE,
+            "kernel stack area exhausted"
+        );
+        let layout = Layout::from_size_align(size, PAGE_SIZE).unwrap();
+        let frames = unsafe { alloc::alloc::alloc(layout) };
+        if frames.is_null() {
+            alloc::alloc::handle_alloc_error(layout);
+        }
+        let mut pt = PageTable::init(phys_to_virt(kernel_page_table_root()), 0);
+        pt.map(
+            bottom,
+            virt_to_phys(frames as usize),
+            size,
+            PAGE_SIZE,
+            MappingFlags::KERNEL_RW,
+        )
+        .expect("failed to map kernel stack");
+        area.next = bottom + size;
+        drop(area);
+        Self { bottom, size }.filled()
+    }
+
+    fn filled(self) -> Self {
+        let words = self.bottom as *mut u64;
+        unsafe { core::slice::from_raw_parts_mut(words, self.size / 8) }.fill(STACK_PATTERN);
+        self
+    }
+
+    /// Returns the lowest address of the stack.
+    pub fn bottom(&self) -> usize {
+        self.bottom
+    }
+
+    /// Returns the highest address of the stack (exclusive).
+    pub fn top(&self) -> usize {
+        self.bottom + self.size
+    }
+
+    /// Returns the maximum number of bytes of the stack used so far: the
+    /// part below it still holds the fill pattern.
+    pub fn max_usage(&self) -> usize {
+        let bottom = self.bottom as *const u64;
+        let words = self.size / 8;
+        let unused = (0..words)
+            .take_while(|&i| unsafe { bottom.add(i).read_volatile() } == STACK_PATTERN)
+            .count();
+        (words - unused) * 8
+    }
+
+    /// Returns whether `vaddr` is in the guard page below the stack.
+    pub fn guard_contains(&self, vaddr: usize) -> bool {
+        (self.bottom - PAGE_SIZE..self.bottom).contains(&vaddr)
+    }
+}
+
+impl Drop for KernelStack {
+    fn drop(&mut self) {
+        STACK_AREA.lock().free.push((self.bottom, self.size));
+    }
+}
@@ -0,0 +1,70 @@
+//! Memory management: the kernel page table and user address spaces.
+
+#![no_std]
+
+extern crate alloc;
+
+mod asid;
+mod aspace;
+mod kstack;
+
+pub use asid::{MmContext, switch_to};
+pub use aspace::AddrSpace;
+pub use kstack::KernelStack;
+
+use axconfig::{SIZE_2M, phys_to_virt};
+use axhal::mem::MemRegion;
+use axsync::BootOnceCell;
+use page_table::{PageTable, PagingError};
+
+static KERNEL_PAGE_TABLE: BootOnceCell<PageTable<'static>> = BootOnceCell::new();
+
+#[derive(Debug)]
+pub enum MmError {
+    InvalidParam,
+    NotMapped,
+    AlreadyMapped,
+    NoMemory,
+}
+pub type MmResult<T = ()> = Result<T, MmError>;
+
+impl From<PagingError> for MmError {
+    fn from(err: PagingError) -> Self {
+        match err {
+            PagingError::NoMemory => Self::NoMemory,
+            PagingError::NotMapped => Self::NotMapped,
+            PagingError::AlreadyMapped => Self::AlreadyMapped,
+            PagingError::MappedToHugePage => Self::InvalidParam,
+        }
+    }
+}
+
+/// Builds the kernel page table from `regions` (linearly mapped) and
+/// switches the current CPU to it.
+pub fn init_kernel_page_table(regions: impl Iterator<Item = MemRegion>) {
+    let mut kernel_page_table = PageTable::alloc_table(0);
+    for r in regions {
+        let _ = kernel_page_table.map(phys_to_virt(r.paddr), r.paddr, r.size, SIZE_2M, r.flags);
+    }
+    kstack::init(&mut kernel_page_table);
+
+    KERNEL_PAGE_TABLE.init(kernel_page_table);
+    init_kernel_page_table_secondary();
+    asid::init();
+}
+
+/// Switches the current (secondary) CPU to the kernel page table.
+pub fn init_kernel_page_table_secondary() {
+    unsafe { axhal::write_page_table_root(kernel_page_table_root()) };
+}
+
+/// Returns the end of the user half of the address space, which depends on
+/// the paging mode chosen at boot.
+pub fn user_space_end() -> usize {
+    1 << (page_table::aspace_bits() - 1)
+}
+
+/// Returns the physical address of the kernel page table root.
+pub fn kernel_page_table_root() -> usize {
+    KERNEL_PAGE_TABLE.get().root_paddr()
+}
@@ -1,8 +1,10 @@
 #![no_std]
 #![no_main]
 
+mod user_app;
+
 use axstd::sync::Mutex;
-use axstd::{String, Vec, println, thread, time};
+use axstd::{String, Vec, println, process, thread, time};
 
 #[unsafe(no_mangle)]
 pub fn main() {
@@ -19,6 +21,28 @@ pub fn main() {
 
     try_multitask();
 
+    test_thread_local();
+
+    test_sleep();
+
+    test_thread_panic();
+
+    test_thread_kill();
+
+    test_sync_primitives();
+
+    test_handoff_mutex();
+
+    test_priorities();
+
+    test_channels();
+
+    test_executor();
+
+    test_wakeups();
+
+    test_user_apps();
+
     let d = now.elapsed();
     println!("Elapsed: {}.{:06}", d.as_secs(), d.subsec_micros());
 }
@@ -41,6 +65,415 @@ fn try_multitask() {
     println!("Task gets result: {result}");
 }
 
+fn test_thread_local() {
+    use core::cell::Cell;
+
+    axstd::thread_local! {
+        static COUNTER: Cell<usize> = Cell::new(1);
+    }
+
+    COUNTER.set(2);
+    let worker = thread::Builder::new()
+        .name(String::from("worker"))
+        .stack_size(0x10000)
+        .spawn(|| {
+            COUNTER.set(COUNTER.get() + 40);
+            (thread::current().name().map(String::from), COUNTER.get())
+        })
+        .unwrap();
+    let (name, counter) = worker.join().unwrap();
+    assert_eq!(name.as_deref(), Some("worker"));
+    assert_eq!(counter, 41);
+    assert_eq!(COUNTER.get(), 2);
+    println!("Thread-local test run OK!");
+}
+
+fn test_sleep() {
+    use core::time::Duration;
+
+    let sleeper = thread::spawn(|| {
+        let now = time::Instant::now();
+        thread::sleep(Duration::from_millis(50));
+        now.elapsed()
+    });
+    let slept = sleeper.join().unwrap();
+    assert!(slept >= Duration::from_millis(50));
+    println!("Sleep test run OK! slept {}ms", slept.as_millis());
+}
+
+fn test_thread_panic() {
+    let worker = thread::spawn(|| -> usize { panic!("bad worker") });
+    let err = worker.join().unwrap_err();
+    assert_eq!(err, "bad worker");
+
+    let detached = thread::spawn(|| {
+        let v: Vec<usize> = Vec::new();
+        v[1]
+    });
+    while !detached.is_finished() {
+        thread::yield_now();
+    }
+    detached.detach();
+    println!("Thread panic test run OK! join: {err}");
+}
+
+fn test_thread_kill() {
+    use core::time::Duration;
+
+    let sleeper = thread::spawn(|| thread::sleep(Duration::from_secs(3600)));
+    let spinner = thread::spawn(|| {
+        loop {
+            thread::yield_now();
+        }
+    });
+    thread::sleep(Duration::from_millis(10));
+    assert!(sleeper.thread().kill());
+    assert!(spinner.thread().kill());
+    assert!(sleeper.join().is_err());
+    assert!(spinner.join().is_err());
+    println!("Thread kill test run OK!");
+}
+
+fn test_sync_primitives() {
+    extern crate alloc;
+    use alloc::sync::Arc;
+    use axstd::sync::{Barrier, Condvar, LazyLock, OnceLock, RwLock, Semaphore};
+
+    static TABLE: LazyLock<Vec<usize>> = LazyLock::new(|| (0..8).map(|i| i * i).collect());
+    static CONFIG: OnceLock<usize> = OnceLock::new();
+
+    let pair = Arc::new((Mutex::new(false), Condvar::new()));
+    let pair2 = pair.clone();
+    thread::spawn(move || {
+        *pair2.0.lock() = true;
+        pair2.1.notify_one();
+    });
+    let ready = pair.1.wait_while(pair.0.lock(), |ready| !*ready);
+    assert!(*ready);
+    drop(ready);
+
+    const N: usize = 4;
+    let barrier = Arc::new(Barrier::new(N));
+    let rwlock = Arc::new(RwLock::new(0));
+    let sem = Arc::new(Semaphore::new(2));
+    let workers: Vec<_> = (0..N)
+        .map(|_| {
+            let (barrier, rwlock, sem) = (barrier.clone(), rwlock.clone(), sem.clone());
+            thread::spawn(move || {
+                sem.acquire();
+                *rwlock.write() += TABLE[2];
+                sem.release();
+                let _ = CONFIG.set(*rwlock.read());
+                barrier.wait().is_leader()
+            })
+        })
+        .collect();
+    let leaders = workers
+        .into_iter()
+        .map(|w| w.join().unwrap())
+        .filter(|&leader| leader)
+        .count();
+    assert_eq!(leaders, 1);
+    assert_eq!(*rwlock.read(), N * 4);
+    assert!(CONFIG.get().is_some());
+    assert_eq!(sem.available_permits(), 2);
+    println!("Sync primitives test run OK!");
+}
+
+fn test_handoff_mutex() {
+    extern crate alloc;
+    use alloc::sync::Arc;
+    use core::time::Duration;
+
+    const N: usize = 4;
+    let order = Arc::new(Mutex::with_handoff(Vec::new()));
+    let guard = order.lock();
+    let workers: Vec<_> = (0..N)
+        .map(|i| {
+            let order = order.clone();
+            let worker = thread::spawn(move || order.lock().push(i));
+            // Lets the worker queue up before the next one.
+            thread::sleep(Duration::from_millis(5));
+            worker
+        })
+        .collect();
+    drop(guard);
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    assert_eq!(*order.lock(), [0, 1, 2, 3]);
+    let stats = order.stats();
+    assert_eq!(stats.acquisitions, N as u64 + 2);
+    println!(
+        "Handoff mutex test run OK! waits: {}, max wait: {}ms",
+        stats.waits,
+        stats.max_wait.as_millis()
+    );
+}
+
+fn test_priorities() {
+    extern crate alloc;
+    use alloc::sync::Arc;
+    use axstd::sync::Semaphore;
+    use core::sync::atomic::{AtomicBool, Ordering::SeqCst};
+    use thread::Priority;
+
+    println!("\nTest priorities ...");
+    // A real-time thread made ready preempts this normal one at once, on
+    // a single CPU.
+    static STARTED: AtomicBool = AtomicBool::new(false);
+    static RAN: AtomicBool = AtomicBool::new(false);
+    let sem = Arc::new(Semaphore::new(0));
+    let sem2 = sem.clone();
+    let rt = thread::spawn(move || {
+        STARTED.store(true, SeqCst);
+        sem2.acquire();
+        RAN.store(true, SeqCst);
+    });
+    assert!(rt.thread().set_priority(Priority::RealTime(10)));
+    assert!(!rt.thread().set_priority(Priority::RealTime(0)));
+    if axstd::SMP == 1 {
+        assert!(STARTED.load(SeqCst));
+    }
+    // Released without yielding: the waiter runs before `release` returns.
+    sem.release();
+    if axstd::SMP == 1 {
+        assert!(RAN.load(SeqCst));
+    }
+    rt.join().unwrap();
+    assert!(RAN.load(SeqCst));
+
+    // A real-time waiter lends its priority to the normal owner until it
+    // unlocks.
+    let lock = Arc::new(Mutex::with_priority_inheritance(0));
+    let guard = lock.lock();
+    let lock2 = lock.clone();
+    let waiter = thread::spawn(move || *lock2.lock() += 1);
+    assert!(waiter.thread().set_priority(Priority::RealTime(20)));
+    let me = thread::current();
+    while me.rt_priority() == 0 {
+        thread::yield_now();
+    }
+    assert_eq!(me.rt_priority(), 20);
+    drop(guard);
+    assert_eq!(me.rt_priority(), 0);
+    waiter.join().unwrap();
+    assert_eq!(*lock.lock(), 1);
+    println!("Priorities test run OK!");
+}
+
+fn test_channels() {
+    use axstd::sync::{mpmc, mpsc};
+    use core::time::Duration;
+
+    // A pipeline: producers -> bounded stage -> consumer.
+    let (tx, rx) = mpsc::channel();
+    let (stage_tx, stage_rx) = mpsc::sync_channel(2);
+    for base in [0, 100] {
+        let tx = tx.clone();
+        thread::spawn(move || {
+            for i in 0..5 {
+                tx.send(base + i).unwrap();
+            }
+        });
+    }
+    drop(tx);
+    thread::spawn(move || {
+        for v in &rx {
+            stage_tx.send(v * 2).unwrap();
+        }
+    });
+    let sum: usize = stage_rx.iter().sum();
+    assert_eq!(sum, 2 * (10 + 510));
+    assert_eq!(stage_rx.try_recv(), Err(mpsc::TryRecvError::Disconnected));
+
+    // A rendezvous returns only once the message was taken.
+    let (tx, rx) = mpsc::sync_channel(0);
+    let taker = thread::spawn(move || rx.recv_timeout(Duration::from_secs(1)));
+    tx.send(7).unwrap();
+    assert_eq!(taker.join().unwrap(), Ok(7));
+    assert!(tx.send(8).is_err());
+
+    // Workers sharing one queue.
+    let (tx, rx) = mpmc::sync_channel(4);
+    let workers: Vec<_> = (0..3)
+        .map(|_| {
+            let rx = rx.clone();
+            thread::spawn(move || rx.iter().count())
+        })
+        .collect();
+    drop(rx);
+    for i in 0..30 {
+        tx.send(i).unwrap();
+    }
+    drop(tx);
+    let handled: usize = workers.into_iter().map(|w| w.join().unwrap()).sum();
+    assert_eq!(handled, 30);
+    println!("Channel test run OK!");
+}
+
+fn test_executor() {
+    extern crate alloc;
+    use alloc::sync::Arc;
+    use axstd::executor::{self, channel};
+    use core::sync::atomic::{AtomicBool, Ordering::SeqCst};
+    use core::time::Duration;
+
+    let start = time::Instant::now();
+    let total = executor::block_on(async {
+        let counter = Arc::new(executor::Mutex::new(0));
+        let (tx, rx) = channel::bounded(2);
+        let tasks: Vec<_> = (0..4u64)
+            .map(|i| {
+                let counter = counter.clone();
+                let tx = tx.clone();
+                executor::spawn(async move {
+                    executor::sleep(Duration::from_millis(10 * (4 - i))).await;
+                    let mut guard = counter.lock().await;
+                    executor::yield_now().await;
+                    *guard += 1;
+                    drop(guard);
+                    tx.send(i).await.unwrap();
+                })
+            })
+            .collect();
+        drop(tx);
+        let mut order = Vec::new();
+        while let Ok(i) = rx.recv().await {
+            order.push(i);
+        }
+        for task in tasks {
+            task.await;
+        }
+        // The shortest sleep finishes first.
+        assert_eq!(order, [3, 2, 1, 0]);
+        *counter.lock().await
+    });
+    assert_eq!(total, 4);
+    assert!(start.elapsed() >= Duration::from_millis(40));
+
+    // Sleeps complete while a task keeps the executor busy.
+    let woke = Arc::new(AtomicBool::new(false));
+    let woke2 = woke.clone();
+    let sleeper = executor::spawn(async move {
+        executor::sleep(Duration::from_millis(10)).await;
+        woke2.store(true, SeqCst);
+    });
+    let spinner = executor::spawn(async move {
+        while !woke.load(SeqCst) {
+            executor::yield_now().await;
+        }
+    });
+    executor::block_on(async {
+        sleeper.await;
+        spinner.await;
+    });
+
+    // A panicking task takes the executor thread down, and it restarts.
+    let panicked = executor::spawn(async { panic!("executor task panic") });
+    assert_eq!(executor::block_on(executor::spawn(async { 7 })), 7);
+    assert!(panicked.is_finished());
+    println!("Executor test run OK!");
+}
+
+fn test_wakeups() {
+    extern crate alloc;
+    use alloc::sync::Arc;
+    use axstd::sync::{Condvar, Semaphore};
+    use core::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+    use core::time::Duration;
+
+    // Permits released while waiters time out or get killed are never
+    // lost: each one is taken by a waiter or still available, and the
+    // waiters that never give up all get one.
+    for round in 0..20u64 {
+        const IMPATIENT: usize = 3;
+        const PATIENT: usize = 3;
+        let sem = Arc::new(Semaphore::new(0));
+        let started = Arc::new(AtomicUsize::new(0));
+        let spawn_waiter = |timeout: Option<Duration>| {
+            let (sem, started) = (sem.clone(), started.clone());
+            thread::spawn(move || {
+                started.fetch_add(1, SeqCst);
+                match timeout {
+                    Some(dur) => sem.acquire_timeout(dur),
+                    None => {
+                        sem.acquire();
+                        true
+                    }
+                }
+            })
+        };
+        let impatient: Vec<_> = (0..IMPATIENT as u64)
+            .map(|i| spawn_waiter(Some(Duration::from_micros((round + i) % 5 * 50))))
+            .collect();
+        let victim = spawn_waiter(None);
+        let patient: Vec<_> = (0..PATIENT).map(|_| spawn_waiter(None)).collect();
+        while started.load(SeqCst) < IMPATIENT + PATIENT + 1 {
+            thread::yield_now();
+        }
+        assert!(victim.thread().kill());
+        for _ in 0..IMPATIENT + PATIENT {
+            sem.release();
+        }
+        assert!(victim.join().is_err());
+        for t in patient {
+            assert!(t.join().unwrap());
+        }
+        let taken = impatient
+            .into_iter()
+            .map(|t| t.join().unwrap())
+            .filter(|&got| got)
+            .count();
+        assert_eq!(taken + sem.available_permits(), IMPATIENT);
+    }
+
+    // Without a release, a timed acquire times out.
+    let sem = Semaphore::new(0);
+    assert!(!sem.acquire_timeout(Duration::from_millis(1)));
+
+    // notify_all wakes every waiter still queued, be it timed or killed.
+    let pair = Arc::new((Mutex::new(false), Condvar::new()));
+    let waiters: Vec<_> = (0..6)
+        .map(|i| {
+            let pair = pair.clone();
+            thread::spawn(move || {
+                let (lock, cvar) = &*pair;
+                if i % 2 == 0 {
+                    let (_, res) =
+                        cvar.wait_timeout_while(lock.lock(), Duration::from_secs(3600), |go| !*go);
+                    !res.timed_out()
+                } else {
+                    drop(cvar.wait_while(lock.lock(), |go| !*go));
+                    true
+                }
+            })
+        })
+        .collect();
+    assert!(waiters[1].thread().kill());
+    *pair.0.lock() = true;
+    pair.1.notify_all();
+    for (i, t) in waiters.into_iter().enumerate() {
+        match t.join() {
+            Ok(notified) => assert!(notified),
+            Err(_) => assert_eq!(i, 1),
+        }
+    }
+    println!("Wakeups test run OK!");
+}
+
+fn test_user_apps() {
+    println!("\nRun user apps ...");
+    let run = |elf: Vec<u8>, name| process::spawn(&elf, name).unwrap().wait().unwrap();
+
+    let code = run(user_app::hello(), "hello");
+    assert_eq!(code, user_app::HELLO_MSG.len() as i32);
+    // -EFAULT, not a fault in the kernel.
+    assert_eq!(run(user_app::bad_write(), "bad_write"), -14);
+    assert_eq!(run(user_app::segfault(), "segfault"), -1);
+    println!("User apps test run OK!");
+}
+
 fn raise_break_exception() {
     unsafe {
         core::arch::asm!("ebreak");
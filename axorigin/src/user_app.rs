@@ -0,0 +1,113 @@
+//! Minimal RISC-V user programs, assembled into ELF images at run time.
+
+use axstd::Vec;
+
+/// Where the single segment of each program is loaded, and its entry.
+const ENTRY: u64 = 0x1000;
+const EHDR_SIZE: usize = 64;
+const PHDR_SIZE: usize = 56;
+
+const ZERO: u32 = 0;
+const A0: u32 = 10;
+const A1: u32 = 11;
+const A2: u32 = 12;
+const A7: u32 = 17;
+
+const SYS_WRITE: i32 = 64;
+const SYS_EXIT: i32 = 93;
+const SYS_SCHED_YIELD: i32 = 124;
+
+const ECALL: u32 = 0x73;
+
+const fn addi(rd: u32, rs1: u32, imm: i32) -> u32 {
+    ((imm as u32 & 0xfff) << 20) | (rs1 << 15) | (rd << 7) | 0x13
+}
+
+const fn li(rd: u32, imm: i32) -> u32 {
+    addi(rd, ZERO, imm)
+}
+
+/// `auipc rd, 0`: loads the address of the instruction itself.
+const fn auipc(rd: u32) -> u32 {
+    (rd << 7) | 0x17
+}
+
+const fn ld(rd: u32, rs1: u32, imm: i32) -> u32 {
+    ((imm as u32 & 0xfff) << 20) | (rs1 << 15) | (3 << 12) | (rd << 7) | 0x03
+}
+
+pub const HELLO_MSG: &[u8] = b"Hello from user space!\n";
+
+/// Writes [`HELLO_MSG`], yields, and exits with what `write` returned.
+pub fn hello() -> Vec<u8> {
+    let code = [
+        auipc(A1),
+        addi(A1, A1, 48), // the message, right after the code
+        li(A0, 1),
+        li(A2, HELLO_MSG.len() as i32),
+        li(A7, SYS_WRITE),
+        ECALL,
+        addi(A2, A0, 0), // survives the yield
+        li(A7, SYS_SCHED_YIELD),
+        ECALL,
+        addi(A0, A2, 0),
+        li(A7, SYS_EXIT),
+        ECALL,
+    ];
+    build_elf(&code, HELLO_MSG)
+}
+
+/// Writes from an unmapped buffer and exits with the error returned.
+pub fn bad_write() -> Vec<u8> {
+    build_elf(
+        &[
+            li(A0, 1),
+            li(A1, 0),
+            li(A2, 1),
+            li(A7, SYS_WRITE),
+            ECALL,
+            li(A7, SYS_EXIT),
+            ECALL,
+        ],
+        &[],
+    )
+}
+
+/// Loads from address 0, which faults.
+pub fn segfault() -> Vec<u8> {
+    build_elf(&[ld(A0, ZERO, 0), li(A7, SYS_EXIT), ECALL], &[])
+}
+
+/// Builds an executable with one readable and executable segment holding
+/// `code` followed by `data`.
+fn build_elf(code: &[u32], data: &[u8]) -> Vec<u8> {
+    let mut seg = Vec::new();
+    for inst in code {
+        seg.extend_from_slice(&inst.to_le_bytes());
+    }
+    seg.extend_from_slice(data);
+
+    let mut elf = [0u8; EHDR_SIZE + PHDR_SIZE].to_vec();
+    elf[0..4].copy_from_slice(b"\x7fELF");
+    elf[4] = 2; // ELFCLASS64
+    elf[5] = 1; // ELFDATA2LSB
+    elf[6] = 1; // EV_CURRENT
+    elf[16..18].copy_from_slice(&2u16.to_le_bytes()); // ET_EXEC
+    elf[18..20].copy_from_slice(&243u16.to_le_bytes()); // EM_RISCV
+    elf[24..32].copy_from_slice(&ENTRY.to_le_bytes());
+    elf[32..40].copy_from_slice(&(EHDR_SIZE as u64).to_le_bytes()); // e_phoff
+    elf[52..54].copy_from_slice(&(EHDR_SIZE as u16).to_le_bytes()); // e_ehsize
+    elf[54..56].copy_from_slice(&(PHDR_SIZE as u16).to_le_bytes()); // e_phentsize
+    elf[56..58].copy_from_slice(&1u16.to_le_bytes()); // e_phnum
+
+    let ph = &mut elf[EHDR_SIZE..];
+    ph[0..4].copy_from_slice(&1u32.to_le_bytes()); // PT_LOAD
+    ph[4..8].copy_from_slice(&5u32.to_le_bytes()); // PF_R | PF_X
+    ph[8..16].copy_from_slice(&((EHDR_SIZE + PHDR_SIZE) as u64).to_le_bytes()); // p_offset
+    ph[16..24].copy_from_slice(&ENTRY.to_le_bytes()); // p_vaddr
+    ph[32..40].copy_from_slice(&(seg.len() as u64).to_le_bytes()); // p_filesz
+    ph[40..48].copy_from_slice(&(seg.len() as u64).to_le_bytes()); // p_memsz
+    ph[48..56].copy_from_slice(&0x1000u64.to_le_bytes()); // p_align
+    elf.extend_from_slice(&seg);
+    elf
+}
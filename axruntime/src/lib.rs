@@ -1,15 +1,24 @@
 #![no_std]
 
-use axconfig::{SIZE_2M, phys_to_virt};
+use axconfig::phys_to_virt;
 pub use axhal::ax_println as println;
 use axhal::mem::{MemRegion, free_regions, kernel_image_regions};
-use axsync::BootOnceCell;
-use page_table::{PAGE_KERNEL_RW, PageTable};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use page_table::MappingFlags;
+mod mp;
 mod trap;
 
 #[macro_use]
 extern crate axlog;
 
+static INITED_CPUS: AtomicUsize = AtomicUsize::new(0);
+/// Number of CPUs that started, `usize::MAX` until all are started.
+static STARTED_CPUS: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+fn is_init_ok() -> bool {
+    INITED_CPUS.load(Ordering::Acquire) == STARTED_CPUS.load(Ordering::Acquire)
+}
+
 #[unsafe(no_mangle)]
 pub extern "C" fn rust_main(hartid: usize, dtb: usize) -> ! {
     unsafe extern "C" {
@@ -27,6 +36,15 @@ pub extern "C" fn rust_main(hartid: usize, dtb: usize) -> ! {
     axlog::set_max_level(option_env!("LOG").unwrap_or(""));
     info!("Logging is enabled.");
     info!("Primary CPU {} started, dtb = {:#x}.", hartid, dtb);
+    let paging_bits = page_table::aspace_bits();
+    if paging_bits < axconfig::ASPACE_BITS {
+        warn!(
+            "Sv{} is not supported, falling back to Sv{}.",
+            axconfig::ASPACE_BITS,
+            paging_bits
+        );
+    }
+    info!("Paging mode: Sv{}.", paging_bits);
     // Parse fdt for early memory info
     let dtb_info = match parse_dtb(dtb) {
         Ok(info) => info,
@@ -43,6 +61,11 @@ pub extern "C" fn rust_main(hartid: usize, dtb: usize) -> ! {
         info!("\t{:#x}, size: {:#x}", r.0, r.1);
     }
 
+    if dtb_info.has_svpbmt {
+        info!("Svpbmt is supported, mapping MMIO as I/O memory.");
+        page_table::enable_svpbmt();
+    }
+
     info!("Initialize kernel page table...");
     remap_kernel_memory(dtb_info);
 
@@ -57,10 +80,21 @@ pub extern "C" fn rust_main(hartid: usize, dtb: usize) -> ! {
     info!("Initialize scheduler...");
     axtask::init_scheduler();
 
+    info!("Start secondary CPUs...");
+    let started = mp::start_secondary_cpus(hartid);
+    STARTED_CPUS.store(started + 1, Ordering::Release);
+
     info!("Initialize interrupt handlers...");
     #[cfg(all(target_os = "none", not(test)))]
     init_interrupt();
 
+    info!("Primary CPU {} init OK.", hartid);
+    INITED_CPUS.fetch_add(1, Ordering::Release);
+
+    while !is_init_ok() {
+        core::hint::spin_loop();
+    }
+
     unsafe {
         main();
     }
@@ -71,7 +105,7 @@ fn remap_kernel_memory(dtb: DtbInfo) {
     let mmio_regions = dtb.mmio_regions.iter().map(|reg| MemRegion {
         paddr: reg.0,
         size: reg.1,
-        flags: PAGE_KERNEL_RW,
+        flags: MappingFlags::READ | MappingFlags::WRITE | MappingFlags::DEVICE,
         name: "mmio",
     });
 
@@ -79,14 +113,7 @@ fn remap_kernel_memory(dtb: DtbInfo) {
         .chain(free_regions(dtb.memory_size))
         .chain(mmio_regions);
 
-    let mut kernel_page_table = PageTable::alloc_table(0);
-    for r in regions {
-        let _ = kernel_page_table.map(phys_to_virt(r.paddr), r.paddr, r.size, SIZE_2M, r.flags);
-    }
-
-    static KERNEL_PAGE_TABLE: BootOnceCell<PageTable> = BootOnceCell::new();
-    KERNEL_PAGE_TABLE.init(kernel_page_table);
-    unsafe { axhal::write_page_table_root(KERNEL_PAGE_TABLE.get().root_paddr()) };
+    axmm::init_kernel_page_table(regions);
 }
 
 extern crate alloc;
@@ -102,6 +129,7 @@ struct DtbInfo {
     memory_addr: usize,
     memory_size: usize,
     mmio_regions: Vec<(usize, usize)>,
+    has_svpbmt: bool,
 }
 
 fn parse_dtb(dtb_pa: usize) -> axdtb::DeviceTreeResult<DtbInfo> {
@@ -112,12 +140,14 @@ fn parse_dtb(dtb_pa: usize) -> axdtb::DeviceTreeResult<DtbInfo> {
         memory_addr: usize,
         memory_size: usize,
         mmio_regions: Vec<(usize, usize)>,
+        has_svpbmt: bool,
     }
 
     let temp_data = Rc::new(RefCell::new(TempData {
         memory_addr: 0,
         memory_size: 0,
         mmio_regions: Vec::new(),
+        has_svpbmt: false,
     }));
 
     // 创建适配器闭包
@@ -129,6 +159,7 @@ fn parse_dtb(dtb_pa: usize) -> axdtb::DeviceTreeResult<DtbInfo> {
         let mut is_memory = false;
         let mut is_mmio = false;
         let mut reg = None;
+        let mut has_svpbmt = false;
 
         for prop in props {
             match prop.0.as_str() {
@@ -143,11 +174,19 @@ fn parse_dtb(dtb_pa: usize) -> axdtb::DeviceTreeResult<DtbInfo> {
                 "reg" => {
                     reg = Some(prop.1);
                 }
+                "riscv,isa" | "riscv,isa-extensions" => {
+                    // "rv64imac_svpbmt" or a list of "svpbmt\0"-like strings
+                    has_svpbmt |= prop
+                        .1
+                        .split(|&b| b == b'_' || b == 0)
+                        .any(|ext| ext == b"svpbmt");
+                }
                 _ => (),
             }
         }
 
         let mut data = temp_data_clone.borrow_mut();
+        data.has_svpbmt |= has_svpbmt;
         if is_memory {
             assert!(addr_cells == 2);
             assert!(size_cells == 2);
@@ -176,6 +215,7 @@ fn parse_dtb(dtb_pa: usize) -> axdtb::DeviceTreeResult<DtbInfo> {
         memory_addr: data.memory_addr,
         memory_size: data.memory_size,
         mmio_regions: data.mmio_regions.clone(),
+        has_svpbmt: data.has_svpbmt,
     })
 }
 
@@ -183,28 +223,10 @@ fn parse_dtb(dtb_pa: usize) -> axdtb::DeviceTreeResult<DtbInfo> {
 fn init_interrupt() {
     use axhal::irq::TIMER_IRQ_NUM;
 
-    // Setup timer interrupt handler
-    const PERIODIC_INTERVAL_NANOS: u64 =
-        axhal::time::NANOS_PER_SEC / axconfig::TICKS_PER_SEC as u64;
-
-    static mut NEXT_DEADLINE: u64 = 0;
-
-    fn update_timer() {
-        let now_ns = axhal::time::current_time_nanos();
-        // Safety: we have disabled preemption in IRQ handler.
-        let mut deadline = unsafe { NEXT_DEADLINE };
-        if now_ns >= deadline {
-            deadline = now_ns + PERIODIC_INTERVAL_NANOS;
-        }
-        unsafe { NEXT_DEADLINE = deadline + PERIODIC_INTERVAL_NANOS };
-        trace!("now {} deadline {}", now_ns, deadline);
-        axhal::time::set_oneshot_timer(deadline);
-    }
-
+    // Setup timer interrupt handler. `axtask` programs the timer for the
+    // next tick or sleeper.
     axhal::irq::register_handler(TIMER_IRQ_NUM, || {
-        update_timer();
-        debug!("On timer tick!");
-        //#[cfg(feature = "multitask")]
+        trace!("On timer tick!");
         axtask::on_timer_tick();
     });
 
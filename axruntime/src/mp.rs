@@ -0,0 +1,60 @@
+use axconfig::{SMP, TASK_STACK_SIZE, virt_to_phys};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+#[unsafe(link_section = ".bss.stack")]
+static mut SECONDARY_BOOT_STACK: [[u8; TASK_STACK_SIZE]; SMP - 1] = [[0; TASK_STACK_SIZE]; SMP - 1];
+
+static ENTERED_CPUS: AtomicUsize = AtomicUsize::new(1);
+
+/// Starts the other harts up to `SMP` one by one. Returns the number of
+/// those that started.
+pub fn start_secondary_cpus(primary_cpu_id: usize) -> usize {
+    let mut logic_cpu_id = 0;
+    for i in 0..SMP {
+        if i != primary_cpu_id {
+            let stack_top = virt_to_phys(unsafe {
+                (&raw const SECONDARY_BOOT_STACK[logic_cpu_id]) as usize + TASK_STACK_SIZE
+            });
+
+            debug!("starting CPU {}...", i);
+            if let Err(err) = axhal::mp::start_secondary_cpu(i, stack_top) {
+                warn!("failed to start CPU {}: {:?}, skipped", i, err);
+                continue;
+            }
+            logic_cpu_id += 1;
+
+            // Bring up secondary CPUs one by one.
+            while ENTERED_CPUS.load(Ordering::Acquire) <= logic_cpu_id {
+                core::hint::spin_loop();
+            }
+        }
+    }
+    logic_cpu_id
+}
+
+/// The main entry point of the ArceOS runtime for secondary CPUs.
+///
+/// It is called from the bootstrapping code in [axhal].
+#[unsafe(no_mangle)]
+pub extern "C" fn rust_main_secondary(cpu_id: usize) -> ! {
+    ENTERED_CPUS.fetch_add(1, Ordering::Release);
+    info!("Secondary CPU {} started.", cpu_id);
+
+    axmm::init_kernel_page_table_secondary();
+
+    axhal::platform_init_secondary();
+
+    axtask::init_scheduler_secondary();
+
+    info!("Secondary CPU {} init OK.", cpu_id);
+    super::INITED_CPUS.fetch_add(1, Ordering::Release);
+
+    while !super::is_init_ok() {
+        core::hint::spin_loop();
+    }
+
+    #[cfg(all(target_os = "none", not(test)))]
+    axhal::irq::enable_irqs();
+
+    axtask::run_idle();
+}
@@ -7,4 +7,18 @@ impl axhal::trap::TrapHandler for TrapHandlerImpl {
     fn handle_irq(irq_num: usize) {
         axhal::irq::dispatch_irq(irq_num);
     }
+
+    fn handle_syscall(syscall_num: usize, args: [usize; 6]) -> isize {
+        axuser::handle_syscall(syscall_num, args)
+    }
+
+    fn handle_page_fault(vaddr: usize, access: page_table::AccessType, is_user: bool) -> bool {
+        if !is_user
+            && let Some(curr) = axtask::current_may_uninit()
+            && curr.in_stack_guard(vaddr)
+        {
+            panic!("stack overflow in task {}", curr.name());
+        }
+        axuser::handle_page_fault(vaddr, access, is_user)
+    }
 }
@@ -0,0 +1,248 @@
+//! Async multi-producer, multi-consumer channels.
+//!
+//! [`Sender::send`] and [`Receiver::recv`] are futures that wait without
+//! blocking the thread. A channel is disconnected once all of its senders
+//! or all of its receivers are dropped.
+
+use super::waker_queue::WakerQueue;
+use crate::sync::mpsc::{RecvError, SendError, TryRecvError, TrySendError};
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::task::{Context, Poll};
+use spinlock::SpinNoIrq;
+
+struct Channel<T> {
+    queue: SpinNoIrq<VecDeque<T>>,
+    /// The bound, `None` for unbounded channels.
+    cap: Option<usize>,
+    senders: AtomicUsize,
+    receivers: AtomicUsize,
+    recv_wakers: WakerQueue,
+    send_wakers: WakerQueue,
+}
+
+impl<T> Channel<T> {
+    fn new(cap: Option<usize>) -> Arc<Self> {
+        Arc::new(Self {
+            queue: SpinNoIrq::new(VecDeque::new()),
+            cap,
+            senders: AtomicUsize::new(1),
+            receivers: AtomicUsize::new(1),
+            recv_wakers: WakerQueue::new(),
+            send_wakers: WakerQueue::new(),
+        })
+    }
+
+    fn is_full(&self, queue: &VecDeque<T>) -> bool {
+        self.cap.is_some_and(|cap| queue.len() >= cap)
+    }
+}
+
+/// Creates an unbounded channel: sending always completes at once.
+pub fn unbounded<T>() -> (Sender<T>, Receiver<T>) {
+    let chan = Channel::new(None);
+    (Sender { chan: chan.clone() }, Receiver { chan })
+}
+
+/// Creates a channel holding up to `cap` messages: sending waits while it
+/// is full.
+///
+/// # Panics
+///
+/// Panics if `cap` is `0`.
+pub fn bounded<T>(cap: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(cap > 0, "capacity must be positive");
+    let chan = Channel::new(Some(cap));
+    (Sender { chan: chan.clone() }, Receiver { chan })
+}
+
+/// The sending half of a channel. It can be cloned.
+pub struct Sender<T> {
+    chan: Arc<Channel<T>>,
+}
+
+/// The receiving half of a channel. It can be cloned: each message goes to
+/// exactly one of the receivers.
+pub struct Receiver<T> {
+    chan: Arc<Channel<T>>,
+}
+
+/// The future returned by [`Sender::send`].
+pub struct Send<'a, T> {
+    chan: &'a Channel<T>,
+    msg: Option<T>,
+    id: Option<u64>,
+}
+
+/// The future returned by [`Receiver::recv`].
+pub struct Recv<'a, T> {
+    chan: &'a Channel<T>,
+    id: Option<u64>,
+}
+
+impl<T> Sender<T> {
+    /// Returns a future that sends `msg` once there is room, or gives it
+    /// back if every receiver is gone.
+    pub fn send(&self, msg: T) -> Send<'_, T> {
+        Send {
+            chan: &self.chan,
+            msg: Some(msg),
+            id: None,
+        }
+    }
+
+    /// Sends `msg` if there is room, without waiting.
+    pub fn try_send(&self, msg: T) -> Result<(), TrySendError<T>> {
+        let chan = &*self.chan;
+        let mut queue = chan.queue.lock();
+        if chan.receivers.load(Ordering::Acquire) == 0 {
+            return Err(TrySendError::Disconnected(msg));
+        }
+        if chan.is_full(&queue) {
+            return Err(TrySendError::Full(msg));
+        }
+        queue.push_back(msg);
+        drop(queue);
+        chan.recv_wakers.wake_one();
+        Ok(())
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Returns a future that takes the next message, or fails once the
+    /// channel is empty and every sender is gone.
+    pub fn recv(&self) -> Recv<'_, T> {
+        Recv {
+            chan: &self.chan,
+            id: None,
+        }
+    }
+
+    /// Takes a message if one is queued, without waiting.
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        let chan = &*self.chan;
+        let msg = chan.queue.lock().pop_front();
+        match msg {
+            Some(msg) => {
+                chan.send_wakers.wake_one();
+                Ok(msg)
+            }
+            None if chan.senders.load(Ordering::Acquire) != 0 => Err(TryRecvError::Empty),
+            None => Err(TryRecvError::Disconnected),
+        }
+    }
+}
+
+impl<T> Future for Send<'_, T> {
+    type Output = Result<(), SendError<T>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let chan = this.chan;
+        let mut queue = chan.queue.lock();
+        if chan.receivers.load(Ordering::Acquire) == 0 {
+            let msg = this.msg.take().expect("`Send` polled after completion");
+            return Poll::Ready(Err(SendError(msg)));
+        }
+        if chan.is_full(&queue) {
+            // Under the queue lock, so a receiver taking a message after
+            // this finds the waker.
+            chan.send_wakers.register(&mut this.id, cx.waker());
+            return Poll::Pending;
+        }
+        queue.push_back(this.msg.take().expect("`Send` polled after completion"));
+        drop(queue);
+        if let Some(id) = this.id.take() {
+            chan.send_wakers.cancel(id);
+        }
+        chan.recv_wakers.wake_one();
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T> Future for Recv<'_, T> {
+    type Output = Result<T, RecvError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let chan = this.chan;
+        let mut queue = chan.queue.lock();
+        let res = match queue.pop_front() {
+            Some(msg) => Ok(msg),
+            None if chan.senders.load(Ordering::Acquire) == 0 => Err(RecvError),
+            None => {
+                chan.recv_wakers.register(&mut this.id, cx.waker());
+                return Poll::Pending;
+            }
+        };
+        drop(queue);
+        if let Some(id) = this.id.take() {
+            chan.recv_wakers.cancel(id);
+        }
+        if res.is_ok() {
+            chan.send_wakers.wake_one();
+        }
+        Poll::Ready(res)
+    }
+}
+
+// The futures only hold a message to send, never a pinned one.
+impl<T> Unpin for Send<'_, T> {}
+
+impl<T> Drop for Send<'_, T> {
+    fn drop(&mut self) {
+        self.chan.send_wakers.leave(self.id);
+    }
+}
+
+impl<T> Drop for Recv<'_, T> {
+    fn drop(&mut self) {
+        self.chan.recv_wakers.leave(self.id);
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.chan.senders.fetch_add(1, Ordering::Relaxed);
+        Self {
+            chan: self.chan.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        // Under the queue lock, so a receiver either sees the count drop or
+        // has registered its waker before.
+        let queue = self.chan.queue.lock();
+        let last = self.chan.senders.fetch_sub(1, Ordering::AcqRel) == 1;
+        drop(queue);
+        if last {
+            self.chan.recv_wakers.wake_all();
+        }
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        self.chan.receivers.fetch_add(1, Ordering::Relaxed);
+        Self {
+            chan: self.chan.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        // Like `Sender::drop`, for the senders waiting for room.
+        let queue = self.chan.queue.lock();
+        let last = self.chan.receivers.fetch_sub(1, Ordering::AcqRel) == 1;
+        drop(queue);
+        if last {
+            self.chan.send_wakers.wake_all();
+        }
+    }
+}
@@ -0,0 +1,270 @@
+//! A small async runtime.
+//!
+//! [`block_on`] drives a future on the calling thread, and [`spawn`] runs a
+//! future on the executor thread, which polls its tasks cooperatively and
+//! is started on first use. A thread with nothing to poll blocks on a wait
+//! queue until a waker unblocks it, or until the next [`sleep`] expires:
+//! the wait registers its deadline with the timer list of `axtask`.
+//!
+//! A task that panics takes the executor thread down with it. A supervisor
+//! thread then starts it again, and awaiting the [`JoinHandle`] of the
+//! panicked task panics.
+
+pub mod channel;
+mod mutex;
+mod timer;
+mod waker_queue;
+
+pub use self::mutex::{Lock, Mutex, MutexGuard};
+pub use self::timer::{Sleep, sleep, sleep_until};
+use crate::sync::{AxWaitQueueHandle, Once, ax_wait_queue_wake};
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use core::cell::UnsafeCell;
+use core::future::Future;
+use core::pin::{Pin, pin};
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll, Waker};
+use spinlock::SpinNoIrq;
+
+/// Unblocks a thread that drives futures.
+struct Notify {
+    wq: AxWaitQueueHandle,
+    notified: AtomicBool,
+}
+
+impl Notify {
+    const fn new(wq: AxWaitQueueHandle) -> Self {
+        Self {
+            wq,
+            notified: AtomicBool::new(false),
+        }
+    }
+
+    fn notify(&self) {
+        if !self.notified.swap(true, Ordering::AcqRel) {
+            ax_wait_queue_wake(&self.wq, 1);
+        }
+    }
+
+    /// Fires the expired sleeps, then blocks until notified or until the
+    /// next sleep expires.
+    fn park(&self) {
+        let timeout = timer::fire_expired();
+        crate::sync::wait_or_exit(&self.wq, || self.notified.load(Ordering::Acquire), timeout);
+        self.notified.store(false, Ordering::Release);
+    }
+}
+
+impl Wake for Notify {
+    fn wake(self: Arc<Self>) {
+        self.notify();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.notify();
+    }
+}
+
+/// Runs `fut` to completion on the current thread, blocking it while the
+/// future is pending.
+pub fn block_on<F: Future>(fut: F) -> F::Output {
+    let notify = Arc::new(Notify::new(AxWaitQueueHandle::new()));
+    let waker = Waker::from(notify.clone());
+    let mut cx = Context::from_waker(&waker);
+    let mut fut = pin!(fut);
+    loop {
+        if let Poll::Ready(res) = fut.as_mut().poll(&mut cx) {
+            return res;
+        }
+        notify.park();
+    }
+}
+
+type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// A spawned future.
+struct Task {
+    /// Only polled by the executor thread, `None` while polled and once
+    /// completed.
+    future: UnsafeCell<Option<BoxFuture>>,
+    /// Whether the task is in the ready queue.
+    queued: AtomicBool,
+    /// Tells the `JoinHandle` that the future panicked.
+    on_panic: Box<dyn Fn() + Send + Sync>,
+}
+
+unsafe impl Sync for Task {}
+
+impl Wake for Task {
+    fn wake(self: Arc<Self>) {
+        if !self.queued.swap(true, Ordering::AcqRel) {
+            EXECUTOR.ready.lock().push_back(self);
+            EXECUTOR.notify.notify();
+        }
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.clone().wake();
+    }
+}
+
+struct Executor {
+    ready: SpinNoIrq<VecDeque<Arc<Task>>>,
+    notify: Notify,
+}
+
+static EXECUTOR: Executor = Executor {
+    ready: SpinNoIrq::new(VecDeque::new()),
+    // The executor thread waits for tasks to poll, not for a resource.
+    notify: Notify::new(AxWaitQueueHandle::new_idle()),
+};
+static STARTED: Once = Once::new();
+/// The task being polled, left there if it panics.
+static POLLING: SpinNoIrq<Option<Arc<Task>>> = SpinNoIrq::new(None);
+
+/// The supervisor thread: runs the executor thread, and starts it again
+/// whenever the task it polls panics.
+fn supervise() {
+    loop {
+        let executor = crate::thread::Builder::new()
+            .name("executor".into())
+            .spawn(run)
+            .expect("failed to spawn the executor thread");
+        // `run` never returns.
+        let _ = executor.join();
+        let panicked = POLLING.lock().take();
+        if let Some(task) = panicked {
+            (task.on_panic)();
+        }
+    }
+}
+
+/// The executor thread: polls the ready tasks in FIFO order. The expired
+/// sleeps are fired on every turn, so that they also complete while other
+/// tasks keep it busy.
+fn run() {
+    loop {
+        timer::fire_expired();
+        let task = EXECUTOR.ready.lock().pop_front();
+        let Some(task) = task else {
+            EXECUTOR.notify.park();
+            continue;
+        };
+        task.queued.store(false, Ordering::Release);
+        // SAFETY: only the executor thread touches the future. It is taken
+        // out while polled, so that a future that panicked is never polled
+        // again.
+        let Some(mut fut) = (unsafe { &mut *task.future.get() }).take() else {
+            continue;
+        };
+        let waker = Waker::from(task.clone());
+        let mut cx = Context::from_waker(&waker);
+        *POLLING.lock() = Some(task.clone());
+        let ready = fut.as_mut().poll(&mut cx).is_ready();
+        POLLING.lock().take();
+        if !ready {
+            unsafe { *task.future.get() = Some(fut) };
+        }
+    }
+}
+
+struct JoinInner<T> {
+    result: Option<T>,
+    panicked: bool,
+    waker: Option<Waker>,
+}
+
+/// An owned permission to await the output of a spawned future. Dropping
+/// it detaches the task.
+pub struct JoinHandle<T> {
+    inner: Arc<SpinNoIrq<JoinInner<T>>>,
+}
+
+/// Spawns `fut` onto the executor thread, starting it if needed.
+pub fn spawn<F>(fut: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    STARTED.call_once(|| {
+        crate::thread::Builder::new()
+            .name("executor-supervisor".into())
+            .spawn(supervise)
+            .expect("failed to spawn the executor supervisor")
+            .detach();
+    });
+    let inner = Arc::new(SpinNoIrq::new(JoinInner {
+        result: None,
+        panicked: false,
+        waker: None,
+    }));
+    let their_inner = inner.clone();
+    let panic_inner = inner.clone();
+    let task = Arc::new(Task {
+        future: UnsafeCell::new(Some(Box::pin(async move {
+            let res = fut.await;
+            finish(&their_inner, |inner| inner.result = Some(res));
+        }))),
+        queued: AtomicBool::new(false),
+        on_panic: Box::new(move || finish(&panic_inner, |inner| inner.panicked = true)),
+    });
+    task.wake();
+    JoinHandle { inner }
+}
+
+/// Records the outcome of a task with `set`, and wakes up its joiner.
+fn finish<T>(inner: &SpinNoIrq<JoinInner<T>>, set: impl FnOnce(&mut JoinInner<T>)) {
+    let waker = {
+        let mut inner = inner.lock();
+        set(&mut inner);
+        inner.waker.take()
+    };
+    if let Some(waker) = waker {
+        waker.wake();
+    }
+}
+
+impl<T> JoinHandle<T> {
+    /// Checks if the task has finished, or panicked.
+    pub fn is_finished(&self) -> bool {
+        let inner = self.inner.lock();
+        inner.result.is_some() || inner.panicked
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = T;
+
+    /// # Panics
+    ///
+    /// Panics if the task panicked.
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut inner = self.inner.lock();
+        if let Some(res) = inner.result.take() {
+            return Poll::Ready(res);
+        }
+        if inner.panicked {
+            drop(inner);
+            panic!("the awaited task panicked");
+        }
+        inner.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+/// Returns a future that yields once to the other futures.
+pub async fn yield_now() {
+    let mut yielded = false;
+    core::future::poll_fn(|cx| {
+        if yielded {
+            return Poll::Ready(());
+        }
+        yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    })
+    .await
+}
@@ -0,0 +1,124 @@
+//! An async mutex.
+
+use super::waker_queue::WakerQueue;
+use core::cell::UnsafeCell;
+use core::future::Future;
+use core::ops::{Deref, DerefMut};
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll};
+
+/// A mutex whose [`lock`](Mutex::lock) is a future: a waiting future
+/// yields to the others instead of blocking the thread. Waiters are woken
+/// up in FIFO order.
+pub struct Mutex<T: ?Sized> {
+    locked: AtomicBool,
+    waiters: WakerQueue,
+    data: UnsafeCell<T>,
+}
+
+/// A guard that provides mutable data access, releasing the lock when
+/// dropped.
+pub struct MutexGuard<'a, T: ?Sized> {
+    lock: &'a Mutex<T>,
+}
+
+/// The future returned by [`Mutex::lock`].
+pub struct Lock<'a, T: ?Sized> {
+    mutex: &'a Mutex<T>,
+    id: Option<u64>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
+unsafe impl<T: ?Sized + Sync> Sync for MutexGuard<'_, T> {}
+
+impl<T> Mutex<T> {
+    /// Creates a new unlocked mutex wrapping the supplied data.
+    pub const fn new(data: T) -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+            waiters: WakerQueue::new(),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Consumes the mutex and unwraps the underlying data.
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// Returns a future that resolves to a guard once the mutex is locked.
+    pub fn lock(&self) -> Lock<'_, T> {
+        Lock {
+            mutex: self,
+            id: None,
+        }
+    }
+
+    /// Locks the mutex if it is unlocked, without waiting.
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        self.locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| MutexGuard { lock: self })
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
+
+impl<'a, T: ?Sized> Future for Lock<'a, T> {
+    type Output = MutexGuard<'a, T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mutex = self.mutex;
+        if let Some(guard) = mutex.try_lock() {
+            if let Some(id) = self.id.take() {
+                mutex.waiters.cancel(id);
+            }
+            return Poll::Ready(guard);
+        }
+        mutex.waiters.register(&mut self.id, cx.waker());
+        // The owner may have unlocked before we got queued.
+        match mutex.try_lock() {
+            Some(guard) => {
+                if let Some(id) = self.id.take() {
+                    mutex.waiters.cancel(id);
+                }
+                Poll::Ready(guard)
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl<T: ?Sized> Drop for Lock<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.waiters.leave(self.id);
+    }
+}
+
+impl<T: ?Sized> Deref for MutexGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.locked.store(false, Ordering::Release);
+        self.lock.waiters.wake_one();
+    }
+}
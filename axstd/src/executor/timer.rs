@@ -0,0 +1,84 @@
+//! Sleep futures, fired by the threads that drive futures.
+
+use alloc::collections::BTreeMap;
+use axhal::time::TimeValue;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::task::{Context, Poll, Waker};
+use core::time::Duration;
+use spinlock::SpinNoIrq;
+
+/// The wakers of the pending sleeps, ordered by deadline (in nanoseconds).
+/// The ID breaks ties between equal deadlines.
+static TIMERS: SpinNoIrq<BTreeMap<(u64, u64), Waker>> = SpinNoIrq::new(BTreeMap::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+fn deadline_nanos(deadline: TimeValue) -> u64 {
+    u64::try_from(deadline.as_nanos()).unwrap_or(u64::MAX)
+}
+
+/// Wakes up the expired sleeps. Returns the time left until the next one,
+/// `None` if there is none.
+pub(super) fn fire_expired() -> Option<Duration> {
+    let now_ns = axhal::time::current_time_nanos();
+    loop {
+        let waker = {
+            let mut timers = TIMERS.lock();
+            match timers.first_entry() {
+                Some(entry) if entry.key().0 <= now_ns => entry.remove(),
+                Some(entry) => return Some(Duration::from_nanos(entry.key().0 - now_ns)),
+                None => return None,
+            }
+        };
+        waker.wake();
+    }
+}
+
+/// A future that completes at a deadline, returned by [`sleep`] and
+/// [`sleep_until`].
+pub struct Sleep {
+    deadline: TimeValue,
+    /// The key of the registered timer, if any.
+    key: Option<(u64, u64)>,
+}
+
+/// Returns a future that completes after `dur`.
+pub fn sleep(dur: Duration) -> Sleep {
+    sleep_until(axhal::time::current_time().saturating_add(dur))
+}
+
+/// Returns a future that completes at `deadline`.
+pub fn sleep_until(deadline: TimeValue) -> Sleep {
+    Sleep {
+        deadline,
+        key: None,
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if axhal::time::current_time() >= self.deadline {
+            if let Some(key) = self.key.take() {
+                TIMERS.lock().remove(&key);
+            }
+            return Poll::Ready(());
+        }
+        let deadline_ns = deadline_nanos(self.deadline);
+        let key = *self
+            .key
+            .get_or_insert_with(|| (deadline_ns, NEXT_ID.fetch_add(1, Ordering::Relaxed)));
+        TIMERS.lock().insert(key, cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some(key) = self.key {
+            TIMERS.lock().remove(&key);
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! Wakers of the futures waiting for an event, in arrival order.
+
+use alloc::collections::VecDeque;
+use core::task::Waker;
+use spinlock::SpinNoIrq;
+
+struct Inner {
+    next_id: u64,
+    wakers: VecDeque<(u64, Waker)>,
+}
+
+/// A FIFO of wakers. Each waiting future keeps the ID of its entry, to
+/// update its waker when polled again and to leave when dropped.
+pub(super) struct WakerQueue {
+    inner: SpinNoIrq<Inner>,
+}
+
+impl WakerQueue {
+    pub(super) const fn new() -> Self {
+        Self {
+            inner: SpinNoIrq::new(Inner {
+                next_id: 1,
+                wakers: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// Queues `waker`, or updates it in place if the entry `id` is still
+    /// queued.
+    pub(super) fn register(&self, id: &mut Option<u64>, waker: &Waker) {
+        let mut inner = self.inner.lock();
+        if let Some(id) = *id
+            && let Some((_, queued)) = inner.wakers.iter_mut().find(|(i, _)| *i == id)
+        {
+            if !queued.will_wake(waker) {
+                queued.clone_from(waker);
+            }
+            return;
+        }
+        let new_id = inner.next_id;
+        inner.next_id += 1;
+        inner.wakers.push_back((new_id, waker.clone()));
+        *id = Some(new_id);
+    }
+
+    /// Removes the entry `id`. Returns `false` if it was already taken by
+    /// a wakeup.
+    pub(super) fn cancel(&self, id: u64) -> bool {
+        let mut inner = self.inner.lock();
+        match inner.wakers.iter().position(|(i, _)| *i == id) {
+            Some(idx) => {
+                inner.wakers.remove(idx);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Wakes up the longest waiter.
+    pub(super) fn wake_one(&self) {
+        let waker = self.inner.lock().wakers.pop_front();
+        if let Some((_, waker)) = waker {
+            waker.wake();
+        }
+    }
+
+    /// Wakes up every waiter.
+    pub(super) fn wake_all(&self) {
+        let wakers = core::mem::take(&mut self.inner.lock().wakers);
+        for (_, waker) in wakers {
+            waker.wake();
+        }
+    }
+
+    /// Leaves the queue on drop of a waiting future. A future that was
+    /// woken up but will not be polled again passes the wakeup on.
+    pub(super) fn leave(&self, id: Option<u64>) {
+        if let Some(id) = id
+            && !self.cancel(id)
+        {
+            self.wake_one();
+        }
+    }
+}
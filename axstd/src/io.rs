@@ -5,6 +5,8 @@ use spinlock::SpinRaw;
 #[derive(Debug)]
 pub enum IoError {
     BadState = 1,
+    InvalidData = 2,
+    Interrupted = 3,
 }
 
 pub type Result<T = ()> = core::result::Result<T, IoError>;
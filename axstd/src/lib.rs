@@ -1,9 +1,13 @@
 #![no_std]
+#![feature(allow_internal_unstable)]
+#![allow(internal_features)]
 
 extern crate alloc;
 extern crate axruntime;
 
+pub mod executor;
 pub mod io;
+pub mod process;
 pub mod sync;
 pub mod thread;
 pub mod time;
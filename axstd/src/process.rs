@@ -0,0 +1,28 @@
+//! User applications running in their own address spaces.
+
+use crate::io::{IoError, Result};
+use alloc::string::ToString;
+
+/// A running user application.
+pub struct Child {
+    inner: axtask::AxTaskRef,
+}
+
+impl Child {
+    /// Returns the identifier of the task running the application.
+    pub fn id(&self) -> u64 {
+        self.inner.id().as_u64()
+    }
+
+    /// Waits for the application to exit and returns its exit code, or
+    /// fails with `Interrupted` if the current thread is killed meanwhile.
+    pub fn wait(self) -> Result<i32> {
+        self.inner.join().ok_or(IoError::Interrupted)
+    }
+}
+
+/// Loads the ELF executable `elf_data` and runs it in U mode.
+pub fn spawn(elf_data: &[u8], name: &str) -> Result<Child> {
+    let inner = axuser::spawn(elf_data, name.to_string()).map_err(|_| IoError::InvalidData)?;
+    Ok(Child { inner })
+}
@@ -0,0 +1,69 @@
+//! A sleeping barrier.
+
+use super::AxWaitQueueHandle;
+use spinlock::SpinNoIrq;
+
+struct BarrierState {
+    count: usize,
+    generation: usize,
+}
+
+/// A barrier enables multiple threads to synchronize the beginning of some
+/// computation, similar to
+/// [`std::sync::Barrier`](https://doc.rust-lang.org/std/sync/struct.Barrier.html).
+pub struct Barrier {
+    wq: AxWaitQueueHandle,
+    state: SpinNoIrq<BarrierState>,
+    num_threads: usize,
+}
+
+/// A `BarrierWaitResult` is returned by [`Barrier::wait()`] when all
+/// threads in the [`Barrier`] have rendezvoused.
+#[derive(Debug)]
+pub struct BarrierWaitResult(bool);
+
+impl BarrierWaitResult {
+    /// Returns `true` if this thread is the "leader thread" for the call
+    /// to [`Barrier::wait()`]: exactly one thread of each round is.
+    pub fn is_leader(&self) -> bool {
+        self.0
+    }
+}
+
+impl Barrier {
+    /// Creates a new barrier that can block `n` threads. A barrier of `0`
+    /// threads behaves like one of `1`.
+    pub const fn new(n: usize) -> Self {
+        Self {
+            wq: AxWaitQueueHandle::new(),
+            state: SpinNoIrq::new(BarrierState {
+                count: 0,
+                generation: 0,
+            }),
+            num_threads: n,
+        }
+    }
+
+    /// Blocks the current thread until all threads have rendezvoused here.
+    /// The barrier is reusable once they all have.
+    pub fn wait(&self) -> BarrierWaitResult {
+        let mut state = self.state.lock();
+        let generation = state.generation;
+        state.count += 1;
+        if state.count < self.num_threads {
+            drop(state);
+            super::wait_or_exit(
+                &self.wq,
+                || self.state.lock().generation != generation,
+                None,
+            );
+            BarrierWaitResult(false)
+        } else {
+            state.count = 0;
+            state.generation = generation.wrapping_add(1);
+            drop(state);
+            super::ax_wait_queue_wake(&self.wq, u32::MAX);
+            BarrierWaitResult(true)
+        }
+    }
+}
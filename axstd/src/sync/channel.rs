@@ -0,0 +1,218 @@
+//! The queue shared by the `mpsc` and `mpmc` channels.
+
+use super::AxWaitQueueHandle;
+use super::mpsc::{RecvTimeoutError, SendError, TryRecvError, TrySendError};
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use axhal::time::TimeValue;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use spinlock::SpinNoIrq;
+
+struct State<T> {
+    queue: VecDeque<T>,
+    /// Number of messages taken so far.
+    received: u64,
+    /// Number of receivers sleeping for a message.
+    waiting: usize,
+}
+
+struct Channel<T> {
+    state: SpinNoIrq<State<T>>,
+    /// The bound, `None` for unbounded channels. A bound of `0` makes the
+    /// senders wait for a receiver to take each message.
+    cap: Option<usize>,
+    senders: AtomicUsize,
+    receivers: AtomicUsize,
+    /// Receivers waiting for a message.
+    recv_wq: AxWaitQueueHandle,
+    /// Senders waiting for room, or for a receiver to take their message.
+    send_wq: AxWaitQueueHandle,
+}
+
+impl<T> Channel<T> {
+    fn is_full(&self, state: &State<T>) -> bool {
+        self.cap.is_some_and(|cap| state.queue.len() >= cap.max(1))
+    }
+
+    fn has_senders(&self) -> bool {
+        self.senders.load(Ordering::Acquire) != 0
+    }
+
+    fn has_receivers(&self) -> bool {
+        self.receivers.load(Ordering::Acquire) != 0
+    }
+}
+
+/// Creates a channel of the given bound, `None` for an unbounded one.
+pub(super) fn new<T>(cap: Option<usize>) -> (Tx<T>, Rx<T>) {
+    let chan = Arc::new(Channel {
+        state: SpinNoIrq::new(State {
+            queue: VecDeque::new(),
+            received: 0,
+            waiting: 0,
+        }),
+        cap,
+        senders: AtomicUsize::new(1),
+        receivers: AtomicUsize::new(1),
+        recv_wq: AxWaitQueueHandle::new(),
+        send_wq: AxWaitQueueHandle::new(),
+    });
+    (Tx(chan.clone()), Rx(chan))
+}
+
+/// The sending half of a channel.
+pub(super) struct Tx<T>(Arc<Channel<T>>);
+
+impl<T> Tx<T> {
+    pub(super) fn send(&self, msg: T) -> Result<(), SendError<T>> {
+        let chan = &*self.0;
+        let seq = loop {
+            let mut state = chan.state.lock();
+            if !chan.has_receivers() {
+                return Err(SendError(msg));
+            }
+            if !chan.is_full(&state) {
+                state.queue.push_back(msg);
+                break state.received + state.queue.len() as u64;
+            }
+            drop(state);
+            super::wait_or_exit(
+                &chan.send_wq,
+                || !chan.is_full(&chan.state.lock()) || !chan.has_receivers(),
+                None,
+            );
+        };
+        super::ax_wait_queue_wake(&chan.recv_wq, 1);
+        if chan.cap == Some(0) {
+            super::wait_or_exit(
+                &chan.send_wq,
+                || chan.state.lock().received >= seq || !chan.has_receivers(),
+                None,
+            );
+            let mut state = chan.state.lock();
+            if state.received < seq {
+                // Nobody took it: it is the only message queued.
+                return Err(SendError(state.queue.pop_back().unwrap()));
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) fn try_send(&self, msg: T) -> Result<(), TrySendError<T>> {
+        let chan = &*self.0;
+        let mut state = chan.state.lock();
+        if !chan.has_receivers() {
+            return Err(TrySendError::Disconnected(msg));
+        }
+        // A rendezvous only happens with a receiver already waiting.
+        if chan.is_full(&state) || (chan.cap == Some(0) && state.waiting == 0) {
+            return Err(TrySendError::Full(msg));
+        }
+        state.queue.push_back(msg);
+        drop(state);
+        super::ax_wait_queue_wake(&chan.recv_wq, 1);
+        Ok(())
+    }
+}
+
+impl<T> Clone for Tx<T> {
+    fn clone(&self) -> Self {
+        self.0.senders.fetch_add(1, Ordering::Relaxed);
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Drop for Tx<T> {
+    fn drop(&mut self) {
+        if self.0.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            super::ax_wait_queue_wake(&self.0.recv_wq, u32::MAX);
+        }
+    }
+}
+
+/// The receiving half of a channel.
+pub(super) struct Rx<T>(Arc<Channel<T>>);
+
+impl<T> Rx<T> {
+    /// Takes a message, blocking until one arrives, every sender is gone,
+    /// or `deadline` has passed.
+    pub(super) fn recv_deadline(&self, deadline: Option<TimeValue>) -> Result<T, RecvTimeoutError> {
+        let chan = &*self.0;
+        loop {
+            let mut state = chan.state.lock();
+            if let Some(msg) = state.queue.pop_front() {
+                state.received += 1;
+                drop(state);
+                self.wake_senders();
+                return Ok(msg);
+            }
+            if !chan.has_senders() {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            let timeout = match deadline {
+                Some(deadline) => {
+                    let now = axhal::time::current_time();
+                    if now >= deadline {
+                        return Err(RecvTimeoutError::Timeout);
+                    }
+                    Some(deadline - now)
+                }
+                None => None,
+            };
+            state.waiting += 1;
+            drop(state);
+            let res = super::ax_wait_queue_wait(
+                &chan.recv_wq,
+                || !chan.state.lock().queue.is_empty() || !chan.has_senders(),
+                timeout,
+            );
+            // Also before exiting, or a rendezvous `try_send` would count
+            // on this receiver.
+            chan.state.lock().waiting -= 1;
+            if res.is_err() {
+                super::exit_killed();
+            }
+        }
+    }
+
+    pub(super) fn try_recv(&self) -> Result<T, TryRecvError> {
+        let chan = &*self.0;
+        let mut state = chan.state.lock();
+        match state.queue.pop_front() {
+            Some(msg) => {
+                state.received += 1;
+                drop(state);
+                self.wake_senders();
+                Ok(msg)
+            }
+            None if chan.has_senders() => Err(TryRecvError::Empty),
+            None => Err(TryRecvError::Disconnected),
+        }
+    }
+
+    /// Wakes up the senders waiting for the message just taken.
+    fn wake_senders(&self) {
+        match self.0.cap {
+            // Nobody waits on an unbounded channel.
+            None => {}
+            // Each sender waits for its own message to be taken.
+            Some(0) => super::ax_wait_queue_wake(&self.0.send_wq, u32::MAX),
+            Some(_) => super::ax_wait_queue_wake(&self.0.send_wq, 1),
+        }
+    }
+}
+
+impl<T> Clone for Rx<T> {
+    fn clone(&self) -> Self {
+        self.0.receivers.fetch_add(1, Ordering::Relaxed);
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Drop for Rx<T> {
+    fn drop(&mut self) {
+        if self.0.receivers.fetch_sub(1, Ordering::AcqRel) == 1 {
+            super::ax_wait_queue_wake(&self.0.send_wq, u32::MAX);
+        }
+    }
+}
@@ -0,0 +1,48 @@
+//! A value initialized on first access.
+
+use super::OnceLock;
+use core::cell::UnsafeCell;
+use core::ops::Deref;
+
+/// A value which is initialized on the first access, similar to
+/// [`std::sync::LazyLock`](https://doc.rust-lang.org/std/sync/struct.LazyLock.html).
+pub struct LazyLock<T, F = fn() -> T> {
+    cell: OnceLock<T>,
+    init: UnsafeCell<Option<F>>,
+}
+
+// Same unsafe impls as `std::sync::LazyLock`: `init` is only touched by
+// the thread that runs the initialization.
+unsafe impl<T: Sync + Send, F: Send> Sync for LazyLock<T, F> {}
+
+impl<T, F: FnOnce() -> T> LazyLock<T, F> {
+    /// Creates a new lazy value with the given initializing function.
+    pub const fn new(f: F) -> Self {
+        Self {
+            cell: OnceLock::new(),
+            init: UnsafeCell::new(Some(f)),
+        }
+    }
+
+    /// Forces the evaluation of this lazy value and returns a reference to
+    /// the result.
+    pub fn force(this: &Self) -> &T {
+        this.cell.get_or_init(|| {
+            let init = unsafe { (*this.init.get()).take() };
+            init.expect("LazyLock instance has previously been poisoned")()
+        })
+    }
+}
+
+impl<T, F: FnOnce() -> T> Deref for LazyLock<T, F> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        Self::force(self)
+    }
+}
+
+impl<T: Default> Default for LazyLock<T> {
+    fn default() -> Self {
+        Self::new(T::default)
+    }
+}
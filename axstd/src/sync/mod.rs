@@ -1,6 +1,25 @@
+mod barrier;
+mod channel;
+mod condvar;
+mod lazy_lock;
+pub mod mpmc;
+pub mod mpsc;
 mod mutex;
+mod once;
+mod once_lock;
+mod rwlock;
+mod semaphore;
 
-pub use self::mutex::{Mutex, MutexGuard};
+pub use self::barrier::{Barrier, BarrierWaitResult};
+pub use self::condvar::{Condvar, WaitTimeoutResult};
+pub use self::lazy_lock::LazyLock;
+pub use self::mutex::{Mutex, MutexGuard, MutexStats};
+pub use self::once::Once;
+pub use self::once_lock::OnceLock;
+pub use self::rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+pub use self::semaphore::Semaphore;
+use crate::io::{IoError, Result};
+use axtask::AxTaskRef;
 use core::time::Duration;
 
 /// A handle to a wait queue.
@@ -14,26 +33,50 @@ impl AxWaitQueueHandle {
     pub const fn new() -> Self {
         Self(axtask::WaitQueue::new())
     }
+
+    /// Creates a new empty wait queue for threads idly waiting for work,
+    /// which are never reported as hung.
+    pub const fn new_idle() -> Self {
+        Self(axtask::WaitQueue::new_idle())
+    }
 }
 
 pub fn ax_current_task_id() -> u64 {
     axtask::current().id().as_u64()
 }
 
+/// Blocks until `until_condition` becomes true or `timeout` has passed.
+/// Returns whether it timed out, or `Interrupted` if the thread was killed.
 pub fn ax_wait_queue_wait(
     wq: &AxWaitQueueHandle,
     until_condition: impl Fn() -> bool,
     timeout: Option<Duration>,
+) -> Result<bool> {
+    let res = match timeout {
+        Some(dur) => wq.0.wait_timeout_until(dur, until_condition),
+        None => wq.0.wait_until(until_condition).map(|_| false),
+    };
+    res.map_err(|_| IoError::Interrupted)
+}
+
+/// Like [`ax_wait_queue_wait`], for the primitives whose waits cannot fail:
+/// a killed thread exits instead.
+pub(crate) fn wait_or_exit(
+    wq: &AxWaitQueueHandle,
+    until_condition: impl Fn() -> bool,
+    timeout: Option<Duration>,
 ) -> bool {
-    if let Some(_dur) = timeout {
-        unimplemented!();
-    }
+    ax_wait_queue_wait(wq, until_condition, timeout).unwrap_or_else(|_| exit_killed())
+}
 
-    if timeout.is_some() {
-        panic!("ax_wait_queue_wait: the `timeout` argument is ignored without the `irq` feature");
-    }
-    wq.0.wait_until(until_condition);
-    false
+/// Exits the current thread, which was killed while waiting.
+pub(crate) fn exit_killed() -> ! {
+    axtask::exit(axtask::KILL_EXIT_CODE)
+}
+
+/// Wakes up `task` if it waits in `wq`.
+pub fn ax_wait_queue_wake_task(wq: &AxWaitQueueHandle, task: &AxTaskRef) {
+    wq.0.notify_task(true, task);
 }
 
 pub fn ax_wait_queue_wake(wq: &AxWaitQueueHandle, count: u32) {
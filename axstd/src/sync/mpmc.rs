@@ -0,0 +1,106 @@
+//! Multi-producer, multi-consumer FIFO queue communication primitives,
+//! similar to [`std::sync::mpmc`](https://doc.rust-lang.org/std/sync/mpmc/index.html).
+//!
+//! Unlike [`mpsc`](super::mpsc), the receivers can be cloned too: each
+//! message goes to exactly one of them.
+
+use super::channel::{self, Rx, Tx};
+use core::time::Duration;
+
+pub use super::mpsc::{
+    Iter, RecvError, RecvTimeoutError, SendError, TryIter, TryRecvError, TrySendError,
+};
+
+/// Creates a new unbounded channel: sending never blocks.
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let (tx, rx) = channel::new(None);
+    (Sender { inner: tx }, Receiver { inner: rx })
+}
+
+/// Creates a new bounded channel holding up to `bound` messages: sending
+/// blocks while it is full. With a `bound` of `0`, each send blocks until
+/// a receiver takes the message.
+pub fn sync_channel<T>(bound: usize) -> (Sender<T>, Receiver<T>) {
+    let (tx, rx) = channel::new(Some(bound));
+    (Sender { inner: tx }, Receiver { inner: rx })
+}
+
+/// The sending half of a channel. It can be cloned to send from several
+/// threads.
+pub struct Sender<T> {
+    inner: Tx<T>,
+}
+
+impl<T> Sender<T> {
+    /// Sends a message on the channel, blocking while a bounded channel is
+    /// full. Gives the message back if every receiver is gone.
+    pub fn send(&self, msg: T) -> Result<(), SendError<T>> {
+        self.inner.send(msg)
+    }
+
+    /// Sends a message on the channel if there is room, without blocking.
+    pub fn try_send(&self, msg: T) -> Result<(), TrySendError<T>> {
+        self.inner.try_send(msg)
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+/// The receiving half of a channel. It can be cloned to receive from
+/// several threads.
+pub struct Receiver<T> {
+    inner: Rx<T>,
+}
+
+impl<T> Receiver<T> {
+    /// Takes a message, blocking until one arrives. Fails once the channel
+    /// is empty and every sender is gone.
+    pub fn recv(&self) -> Result<T, RecvError> {
+        self.inner.recv_deadline(None).map_err(|_| RecvError)
+    }
+
+    /// Takes a message if one is queued, without blocking.
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.inner.try_recv()
+    }
+
+    /// Takes a message, blocking until one arrives or `timeout` has passed.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        let deadline = axhal::time::current_time().saturating_add(timeout);
+        self.inner.recv_deadline(Some(deadline))
+    }
+
+    /// Returns an iterator that blocks for each message, and ends once
+    /// every sender is gone.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter::new(&self.inner)
+    }
+
+    /// Returns an iterator over the messages queued now.
+    pub fn try_iter(&self) -> TryIter<'_, T> {
+        TryIter::new(&self.inner)
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Receiver<T> {
+    type Item = T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
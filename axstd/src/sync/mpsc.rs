@@ -0,0 +1,208 @@
+//! Multi-producer, single-consumer FIFO queue communication primitives,
+//! similar to [`std::sync::mpsc`](https://doc.rust-lang.org/std/sync/mpsc/index.html).
+//!
+//! A channel is disconnected once all of its senders or its receiver are
+//! dropped: the other side then gets an error instead of blocking forever.
+
+use super::channel::{self, Rx, Tx};
+use core::fmt;
+use core::time::Duration;
+
+/// An error returned from [`Sender::send`] or [`SyncSender::send`] when
+/// the receiver is gone. It gives the message back.
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub struct SendError<T>(pub T);
+
+/// An error returned from [`Receiver::recv`] when every sender is gone and
+/// the channel is empty.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct RecvError;
+
+/// An error returned from [`Receiver::try_recv`].
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum TryRecvError {
+    /// The channel is empty for now.
+    Empty,
+    /// The channel is empty and every sender is gone.
+    Disconnected,
+}
+
+/// An error returned from [`Receiver::recv_timeout`].
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum RecvTimeoutError {
+    /// No message arrived in time.
+    Timeout,
+    /// The channel is empty and every sender is gone.
+    Disconnected,
+}
+
+/// An error returned from [`SyncSender::try_send`]. It gives the message
+/// back.
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum TrySendError<T> {
+    /// The channel is full, or no receiver waits on a rendezvous channel.
+    Full(T),
+    /// The receiver is gone.
+    Disconnected(T),
+}
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SendError").finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Debug for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrySendError::Full(..) => f.write_str("Full(..)"),
+            TrySendError::Disconnected(..) => f.write_str("Disconnected(..)"),
+        }
+    }
+}
+
+/// Creates a new asynchronous channel: sending never blocks, as the queue
+/// is unbounded.
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let (tx, rx) = channel::new(None);
+    (Sender { inner: tx }, Receiver { inner: rx })
+}
+
+/// Creates a new synchronous channel holding up to `bound` messages:
+/// sending blocks while it is full. With a `bound` of `0`, each send
+/// blocks until a receiver takes the message.
+pub fn sync_channel<T>(bound: usize) -> (SyncSender<T>, Receiver<T>) {
+    let (tx, rx) = channel::new(Some(bound));
+    (SyncSender { inner: tx }, Receiver { inner: rx })
+}
+
+/// The sending half of a [`channel`]. It can be cloned to send from
+/// several threads.
+pub struct Sender<T> {
+    inner: Tx<T>,
+}
+
+impl<T> Sender<T> {
+    /// Sends a message on the channel, or gives it back if the receiver is
+    /// gone.
+    pub fn send(&self, msg: T) -> Result<(), SendError<T>> {
+        self.inner.send(msg)
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+/// The sending half of a [`sync_channel`]. It can be cloned to send from
+/// several threads.
+pub struct SyncSender<T> {
+    inner: Tx<T>,
+}
+
+impl<T> SyncSender<T> {
+    /// Sends a message on the channel, blocking while it is full. Gives
+    /// the message back if the receiver is gone.
+    pub fn send(&self, msg: T) -> Result<(), SendError<T>> {
+        self.inner.send(msg)
+    }
+
+    /// Sends a message on the channel if there is room, without blocking.
+    pub fn try_send(&self, msg: T) -> Result<(), TrySendError<T>> {
+        self.inner.try_send(msg)
+    }
+}
+
+impl<T> Clone for SyncSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+/// The receiving half of a [`channel`] or [`sync_channel`].
+pub struct Receiver<T> {
+    inner: Rx<T>,
+}
+
+impl<T> Receiver<T> {
+    /// Takes a message, blocking until one arrives. Fails once the channel
+    /// is empty and every sender is gone.
+    pub fn recv(&self) -> Result<T, RecvError> {
+        self.inner.recv_deadline(None).map_err(|_| RecvError)
+    }
+
+    /// Takes a message if one is queued, without blocking.
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.inner.try_recv()
+    }
+
+    /// Takes a message, blocking until one arrives or `timeout` has passed.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        let deadline = axhal::time::current_time().saturating_add(timeout);
+        self.inner.recv_deadline(Some(deadline))
+    }
+
+    /// Returns an iterator that blocks for each message, and ends once
+    /// every sender is gone.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter::new(&self.inner)
+    }
+
+    /// Returns an iterator over the messages queued now.
+    pub fn try_iter(&self) -> TryIter<'_, T> {
+        TryIter::new(&self.inner)
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Receiver<T> {
+    type Item = T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+/// An iterator over the messages of a receiver, blocking for each.
+pub struct Iter<'a, T: 'a> {
+    rx: &'a Rx<T>,
+}
+
+/// An iterator over the messages already queued on a receiver.
+pub struct TryIter<'a, T: 'a> {
+    rx: &'a Rx<T>,
+}
+
+impl<'a, T> Iter<'a, T> {
+    pub(super) fn new(rx: &'a Rx<T>) -> Self {
+        Self { rx }
+    }
+}
+
+impl<'a, T> TryIter<'a, T> {
+    pub(super) fn new(rx: &'a Rx<T>) -> Self {
+        Self { rx }
+    }
+}
+
+impl<T> Iterator for Iter<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.rx.recv_deadline(None).ok()
+    }
+}
+
+impl<T> Iterator for TryIter<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.rx.try_recv().ok()
+    }
+}
@@ -1,22 +1,70 @@
-//! A naïve sleeping mutex.
+//! A sleeping mutex.
 
 use super::AxWaitQueueHandle;
+use alloc::collections::VecDeque;
+use axtask::AxTaskRef;
 use core::cell::UnsafeCell;
 use core::ops::{Deref, DerefMut};
-use core::sync::atomic::{AtomicU64, Ordering};
+use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use core::time::Duration;
+use spinlock::SpinNoIrq;
+
+/// The most a locker spins before sleeping.
+const MAX_SPINS: u32 = 100;
 
 /// A mutual exclusion primitive useful for protecting shared data, similar to
 /// [`std::sync::Mutex`](https://doc.rust-lang.org/std/sync/struct.Mutex.html).
 ///
-/// When the mutex is locked, the current task will block and be put into the
-/// wait queue. When the mutex is unlocked, all tasks waiting on the queue
-/// will be woken up.
+/// When the mutex is locked, the current task spins for a while in case the
+/// owner releases it soon, then blocks and is put into the wait queue. When
+/// the mutex is unlocked, one task waiting on the queue is woken up and
+/// retries, racing with the new lockers.
+///
+/// A mutex made by [`Mutex::with_handoff`] is instead passed directly to
+/// its longest waiter on unlock, so no waiter starves. A mutex made by
+/// [`Mutex::with_priority_inheritance`] lends the real-time priority of its
+/// waiters to its owner, so that a lower-priority owner cannot keep a
+/// real-time waiter blocked for long.
 pub struct Mutex<T: ?Sized> {
     wq: AxWaitQueueHandle,
     owner_id: AtomicU64,
+    /// The owner task, kept by priority-inheriting mutexes only.
+    pi_owner: Option<SpinNoIrq<Option<AxTaskRef>>>,
+    /// The waiters in arrival order, kept by handoff mutexes only.
+    waiters: Option<SpinNoIrq<VecDeque<AxTaskRef>>>,
+    /// The spins the recent contended acquisitions needed.
+    spins: AtomicU32,
+    stats: Counters,
     data: UnsafeCell<T>,
 }
 
+/// Contention counters of a [`Mutex`], as returned by [`Mutex::stats`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MutexStats {
+    /// Number of times the mutex was locked.
+    pub acquisitions: u64,
+    /// Number of those that found it locked and had to spin or sleep.
+    pub waits: u64,
+    /// The longest of those waits.
+    pub max_wait: Duration,
+}
+
+struct Counters {
+    acquisitions: AtomicU64,
+    waits: AtomicU64,
+    max_wait_ns: AtomicU64,
+}
+
+impl Counters {
+    const fn new() -> Self {
+        Self {
+            acquisitions: AtomicU64::new(0),
+            waits: AtomicU64::new(0),
+            max_wait_ns: AtomicU64::new(0),
+        }
+    }
+}
+
 /// A guard that provides mutable data access.
 ///
 /// When the guard falls out of scope it will release the lock.
@@ -36,6 +84,46 @@ impl<T> Mutex<T> {
         Self {
             wq: AxWaitQueueHandle::new(),
             owner_id: AtomicU64::new(0),
+            pi_owner: None,
+            waiters: None,
+            spins: AtomicU32::new(0),
+            stats: Counters::new(),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Creates a new [`Mutex`] wrapping the supplied data, which is handed
+    /// over to its waiters in FIFO order.
+    ///
+    /// New lockers never overtake the waiters, at the cost of a context
+    /// switch on every contended unlock.
+    #[inline(always)]
+    pub const fn with_handoff(data: T) -> Self {
+        Self {
+            wq: AxWaitQueueHandle::new(),
+            owner_id: AtomicU64::new(0),
+            pi_owner: None,
+            waiters: Some(SpinNoIrq::new(VecDeque::new())),
+            spins: AtomicU32::new(0),
+            stats: Counters::new(),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Creates a new priority-inheriting [`Mutex`] wrapping the supplied
+    /// data.
+    ///
+    /// Inheritance is not transitive: an owner blocked on another mutex
+    /// does not pass the priority on.
+    #[inline(always)]
+    pub const fn with_priority_inheritance(data: T) -> Self {
+        Self {
+            wq: AxWaitQueueHandle::new(),
+            owner_id: AtomicU64::new(0),
+            pi_owner: Some(SpinNoIrq::new(None)),
+            waiters: None,
+            spins: AtomicU32::new(0),
+            stats: Counters::new(),
             data: UnsafeCell::new(data),
         }
     }
@@ -68,38 +156,169 @@ impl<T: ?Sized> Mutex<T> {
     /// and the lock will be dropped when the guard falls out of scope.
     pub fn lock(&self) -> MutexGuard<T> {
         let current_id = super::ax_current_task_id();
-        loop {
-            // Can fail to lock even if the spinlock is not locked. May be more efficient than `try_lock`
-            // when called in a loop.
-            match self.owner_id.compare_exchange_weak(
-                0,
-                current_id,
-                Ordering::Acquire,
-                Ordering::Relaxed,
-            ) {
-                Ok(_) => break,
-                Err(owner_id) => {
-                    assert_ne!(
-                        owner_id, current_id,
-                        "Thread({}) tried to acquire mutex it already owns.",
-                        current_id,
-                    );
-                    // Wait until the lock looks unlocked before retrying
-                    super::ax_wait_queue_wait(&self.wq, || !self.is_locked(), None);
-                }
-            }
+        let acquired = match &self.pi_owner {
+            Some(pi_owner) => self.lock_pi(pi_owner, current_id),
+            None => self
+                .owner_id
+                .compare_exchange(0, current_id, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok(),
+        };
+        if !acquired {
+            let start_ns = axhal::time::current_time_nanos();
+            self.lock_contended(current_id);
+            let wait_ns = axhal::time::current_time_nanos() - start_ns;
+            self.stats.waits.fetch_add(1, Ordering::Relaxed);
+            self.stats.max_wait_ns.fetch_max(wait_ns, Ordering::Relaxed);
         }
+        self.stats.acquisitions.fetch_add(1, Ordering::Relaxed);
         MutexGuard {
             lock: self,
             data: unsafe { &mut *self.data.get() },
         }
     }
 
+    #[cold]
+    fn lock_contended(&self, current_id: u64) {
+        if self.owner_id.load(Ordering::Relaxed) == current_id {
+            self_deadlock(current_id);
+        }
+        if let Some(pi_owner) = &self.pi_owner {
+            loop {
+                self.wait_unlocked();
+                if self.lock_pi(pi_owner, current_id) {
+                    return;
+                }
+            }
+        }
+        if self.spin(current_id) {
+            return;
+        }
+        if let Some(waiters) = &self.waiters {
+            return self.lock_handoff(waiters, current_id);
+        }
+        // Can fail to lock even if the spinlock is not locked. May be more efficient than `try_lock`
+        // when called in a loop.
+        while self
+            .owner_id
+            .compare_exchange_weak(0, current_id, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            // Wait until the lock looks unlocked before retrying
+            self.wait_unlocked();
+        }
+    }
+
+    /// Spins for a while in case the owner, running on another CPU,
+    /// releases the mutex soon. The budget adapts to the spins the recent
+    /// acquisitions needed, like the adaptive mutexes of glibc.
+    fn spin(&self, current_id: u64) -> bool {
+        if axconfig::SMP == 1 {
+            return false;
+        }
+        let spins = self.spins.load(Ordering::Relaxed);
+        let max = (spins * 2 + 10).min(MAX_SPINS);
+        let mut count = 0;
+        let acquired = loop {
+            if self.owner_id.load(Ordering::Relaxed) == 0
+                && self
+                    .owner_id
+                    .compare_exchange_weak(0, current_id, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+            {
+                break true;
+            }
+            if count == max {
+                break false;
+            }
+            core::hint::spin_loop();
+            count += 1;
+        };
+        // Moves the estimate an eighth of the way to this acquisition.
+        let estimate = spins as i32 + (count as i32 - spins as i32) / 8;
+        self.spins.store(estimate as u32, Ordering::Relaxed);
+        acquired
+    }
+
+    /// Queues up behind the other waiters of a handoff mutex and sleeps
+    /// until the mutex is handed over.
+    fn lock_handoff(&self, waiters: &SpinNoIrq<VecDeque<AxTaskRef>>, current_id: u64) {
+        {
+            // The owner only gets back to `0` with no waiters left.
+            let mut waiters = waiters.lock();
+            if self
+                .owner_id
+                .compare_exchange(0, current_id, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+            {
+                return;
+            }
+            waiters.push_back(axtask::current().as_task_ref().clone());
+        }
+        let handed = || self.owner_id.load(Ordering::Acquire) == current_id;
+        if super::ax_wait_queue_wait(&self.wq, handed, None).is_err() {
+            let mut queue = waiters.lock();
+            if handed() {
+                drop(queue);
+                self.hand_off(waiters);
+            } else {
+                queue.retain(|task| task.id().as_u64() != current_id);
+            }
+            super::exit_killed();
+        }
+    }
+
+    /// Passes a handoff mutex to its longest waiter, or unlocks it if none.
+    fn hand_off(&self, waiters: &SpinNoIrq<VecDeque<AxTaskRef>>) {
+        let mut queue = waiters.lock();
+        match queue.pop_front() {
+            Some(next) => {
+                self.owner_id.store(next.id().as_u64(), Ordering::Release);
+                drop(queue);
+                super::ax_wait_queue_wake_task(&self.wq, &next);
+            }
+            None => self.owner_id.store(0, Ordering::Release),
+        }
+    }
+
+    /// Waits until the mutex looks unlocked. As `lock` cannot fail, a
+    /// killed thread exits here.
+    fn wait_unlocked(&self) {
+        super::wait_or_exit(&self.wq, || !self.is_locked(), None);
+    }
+
+    /// Tries to lock a priority-inheriting mutex, or lends the priority of
+    /// the current task to its owner. The owner is updated under the lock
+    /// of `pi_owner` together with `owner_id`, so a waiter always finds it.
+    fn lock_pi(&self, pi_owner: &SpinNoIrq<Option<AxTaskRef>>, current_id: u64) -> bool {
+        let mut owner = pi_owner.lock();
+        match self
+            .owner_id
+            .compare_exchange(0, current_id, Ordering::Acquire, Ordering::Relaxed)
+        {
+            Ok(_) => {
+                *owner = Some(axtask::current().as_task_ref().clone());
+                axtask::pi_lock_acquired();
+                true
+            }
+            Err(owner_id) => {
+                if owner_id == current_id {
+                    self_deadlock(current_id);
+                }
+                if let Some(owner) = owner.as_ref() {
+                    axtask::inherit_priority(owner);
+                }
+                false
+            }
+        }
+    }
+
     /// Try to lock this [`Mutex`], returning a lock guard if successful.
     #[inline(always)]
     pub fn try_lock(&self) -> Option<MutexGuard<T>> {
-        /*
-        let current_id = api::ax_current_task_id();
+        let current_id = super::ax_current_task_id();
+        // The owner of a priority-inheriting mutex is set together with
+        // `owner_id`, as in `lock_pi`.
+        let mut pi_owner = self.pi_owner.as_ref().map(|owner| owner.lock());
         // The reason for using a strong compare_exchange is explained here:
         // https://github.com/Amanieu/parking_lot/pull/207#issuecomment-575869107
         if self
@@ -107,6 +326,11 @@ impl<T: ?Sized> Mutex<T> {
             .compare_exchange(0, current_id, Ordering::Acquire, Ordering::Relaxed)
             .is_ok()
         {
+            if let Some(owner) = pi_owner.as_deref_mut() {
+                *owner = Some(axtask::current().as_task_ref().clone());
+                axtask::pi_lock_acquired();
+            }
+            self.stats.acquisitions.fetch_add(1, Ordering::Relaxed);
             Some(MutexGuard {
                 lock: self,
                 data: unsafe { &mut *self.data.get() },
@@ -114,8 +338,15 @@ impl<T: ?Sized> Mutex<T> {
         } else {
             None
         }
-        */
-        todo!();
+    }
+
+    /// Returns the contention counters of the mutex.
+    pub fn stats(&self) -> MutexStats {
+        MutexStats {
+            acquisitions: self.stats.acquisitions.load(Ordering::Relaxed),
+            waits: self.stats.waits.load(Ordering::Relaxed),
+            max_wait: Duration::from_nanos(self.stats.max_wait_ns.load(Ordering::Relaxed)),
+        }
     }
 
     /// Force unlock the [`Mutex`].
@@ -126,8 +357,25 @@ impl<T: ?Sized> Mutex<T> {
     /// thread. However, this can be useful in some instances for exposing
     /// the lock to FFI that doesn’t know how to deal with RAII.
     pub unsafe fn force_unlock(&self) {
-        let owner_id = self.owner_id.swap(0, Ordering::Release);
         let current_id = super::ax_current_task_id();
+        if let Some(waiters) = &self.waiters {
+            let owner_id = self.owner_id.load(Ordering::Relaxed);
+            assert_eq!(
+                owner_id, current_id,
+                "Thread({}) tried to release mutex it doesn't own",
+                current_id,
+            );
+            self.hand_off(waiters);
+            return;
+        }
+        let owner_id = match &self.pi_owner {
+            Some(pi_owner) => {
+                let mut owner = pi_owner.lock();
+                *owner = None;
+                self.owner_id.swap(0, Ordering::Release)
+            }
+            None => self.owner_id.swap(0, Ordering::Release),
+        };
         assert_eq!(
             owner_id, current_id,
             "Thread({}) tried to release mutex it doesn't own",
@@ -135,6 +383,11 @@ impl<T: ?Sized> Mutex<T> {
         );
         // wake up one waiting thread.
         super::ax_wait_queue_wake(&self.wq, 1);
+        if self.pi_owner.is_some() {
+            // After the wakeup, so that a real-time waiter preempts us as
+            // soon as the inherited priority is dropped.
+            axtask::pi_lock_released();
+        }
     }
 
     /// Returns a mutable reference to the underlying data.
@@ -150,6 +403,21 @@ impl<T: ?Sized> Mutex<T> {
     }
 }
 
+#[cold]
+fn self_deadlock(current_id: u64) -> ! {
+    axtask::report_deadlock(format_args!(
+        "Thread({}) tried to acquire mutex it already owns.",
+        current_id
+    ))
+}
+
+impl<'a, T: ?Sized> MutexGuard<'a, T> {
+    /// Returns the mutex the guard locks.
+    pub(super) fn mutex(&self) -> &'a Mutex<T> {
+        self.lock
+    }
+}
+
 impl<'a, T: ?Sized> Deref for MutexGuard<'a, T> {
     type Target = T;
     #[inline(always)]
@@ -0,0 +1,194 @@
+//! A sleeping reader-writer lock that prefers writers.
+
+use super::AxWaitQueueHandle;
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicUsize, Ordering, fence};
+
+/// Set in `state` while a writer holds the lock. The other bits count the
+/// readers.
+const WRITER: usize = 1 << (usize::BITS - 1);
+
+/// A reader-writer lock, similar to
+/// [`std::sync::RwLock`](https://doc.rust-lang.org/std/sync/struct.RwLock.html).
+///
+/// Writers are preferred: once a writer waits, new readers block until
+/// every waiting writer has had the lock, so a stream of readers cannot
+/// starve the writers.
+pub struct RwLock<T: ?Sized> {
+    read_wq: AxWaitQueueHandle,
+    write_wq: AxWaitQueueHandle,
+    state: AtomicUsize,
+    waiting_writers: AtomicUsize,
+    data: UnsafeCell<T>,
+}
+
+/// A guard that provides shared data access, releasing the read lock when
+/// dropped.
+pub struct RwLockReadGuard<'a, T: ?Sized + 'a> {
+    lock: &'a RwLock<T>,
+}
+
+/// A guard that provides mutable data access, releasing the write lock
+/// when dropped.
+pub struct RwLockWriteGuard<'a, T: ?Sized + 'a> {
+    lock: &'a RwLock<T>,
+}
+
+// Same unsafe impls as `std::sync::RwLock`
+unsafe impl<T: ?Sized + Send> Send for RwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwLock<T> {}
+
+impl<T> RwLock<T> {
+    /// Creates a new unlocked [`RwLock`] wrapping the supplied data.
+    pub const fn new(data: T) -> Self {
+        Self {
+            read_wq: AxWaitQueueHandle::new(),
+            write_wq: AxWaitQueueHandle::new(),
+            state: AtomicUsize::new(0),
+            waiting_writers: AtomicUsize::new(0),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Consumes this [`RwLock`] and unwraps the underlying data.
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    fn can_read(&self) -> bool {
+        self.state.load(Ordering::Relaxed) & WRITER == 0
+            && self.waiting_writers.load(Ordering::Relaxed) == 0
+    }
+
+    /// Locks this [`RwLock`] with shared read access, blocking while a
+    /// writer holds or waits for the lock.
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        loop {
+            if let Some(guard) = self.try_read() {
+                return guard;
+            }
+            super::wait_or_exit(&self.read_wq, || self.can_read(), None);
+        }
+    }
+
+    /// Tries to lock this [`RwLock`] with shared read access.
+    pub fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+        let mut state = self.state.load(Ordering::Relaxed);
+        while state & WRITER == 0 && self.waiting_writers.load(Ordering::Relaxed) == 0 {
+            match self.state.compare_exchange_weak(
+                state,
+                state + 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Some(RwLockReadGuard { lock: self }),
+                Err(s) => state = s,
+            }
+        }
+        None
+    }
+
+    /// Locks this [`RwLock`] with exclusive write access, blocking until no
+    /// other reader or writer holds the lock.
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        if let Some(guard) = self.try_write() {
+            return guard;
+        }
+        self.waiting_writers.fetch_add(1, Ordering::Relaxed);
+        // Pairs with the fence of `write_unlock`: either the unlocker sees
+        // this writer waiting, or the state check below sees it unlocked.
+        fence(Ordering::SeqCst);
+        loop {
+            if self
+                .state
+                .compare_exchange(0, WRITER, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+            {
+                self.waiting_writers.fetch_sub(1, Ordering::Relaxed);
+                return RwLockWriteGuard { lock: self };
+            }
+            let res = super::ax_wait_queue_wait(
+                &self.write_wq,
+                || self.state.load(Ordering::Relaxed) == 0,
+                None,
+            );
+            if res.is_err() {
+                // Let in the readers held back by this writer.
+                if self.waiting_writers.fetch_sub(1, Ordering::Relaxed) == 1 {
+                    super::ax_wait_queue_wake(&self.read_wq, u32::MAX);
+                }
+                super::exit_killed();
+            }
+        }
+    }
+
+    /// Tries to lock this [`RwLock`] with exclusive write access.
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<'_, T>> {
+        self.state
+            .compare_exchange(0, WRITER, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| RwLockWriteGuard { lock: self })
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    fn read_unlock(&self) {
+        if self.state.fetch_sub(1, Ordering::Release) == 1 {
+            super::ax_wait_queue_wake(&self.write_wq, 1);
+        }
+    }
+
+    fn write_unlock(&self) {
+        self.state.store(0, Ordering::Release);
+        fence(Ordering::SeqCst);
+        if self.waiting_writers.load(Ordering::Relaxed) != 0 {
+            super::ax_wait_queue_wake(&self.write_wq, 1);
+        } else {
+            super::ax_wait_queue_wake(&self.read_wq, u32::MAX);
+        }
+    }
+}
+
+impl<T: Default> Default for RwLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<'a, T: ?Sized> Deref for RwLockReadGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for RwLockReadGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.read_unlock();
+    }
+}
+
+impl<'a, T: ?Sized> Deref for RwLockWriteGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for RwLockWriteGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for RwLockWriteGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.write_unlock();
+    }
+}
@@ -0,0 +1,78 @@
+//! A sleeping counting semaphore.
+
+use super::AxWaitQueueHandle;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::time::Duration;
+
+/// A counting semaphore.
+///
+/// [`acquire`](Semaphore::acquire) takes one of the permits, blocking
+/// until one is available, and [`release`](Semaphore::release) gives one
+/// back and wakes up a waiter.
+pub struct Semaphore {
+    wq: AxWaitQueueHandle,
+    permits: AtomicUsize,
+}
+
+impl Semaphore {
+    /// Creates a new semaphore with `permits` available permits.
+    pub const fn new(permits: usize) -> Self {
+        Self {
+            wq: AxWaitQueueHandle::new(),
+            permits: AtomicUsize::new(permits),
+        }
+    }
+
+    /// Returns the number of permits available now.
+    pub fn available_permits(&self) -> usize {
+        self.permits.load(Ordering::Relaxed)
+    }
+
+    /// Takes a permit, blocking until one is available.
+    pub fn acquire(&self) {
+        while !self.try_acquire() {
+            super::wait_or_exit(&self.wq, || self.available_permits() != 0, None);
+        }
+    }
+
+    /// Takes a permit, blocking until one is available or `dur` has
+    /// passed. Returns whether it got one.
+    pub fn acquire_timeout(&self, dur: Duration) -> bool {
+        let deadline = axhal::time::current_time().saturating_add(dur);
+        while !self.try_acquire() {
+            let now = axhal::time::current_time();
+            if now >= deadline {
+                return false;
+            }
+            super::wait_or_exit(
+                &self.wq,
+                || self.available_permits() != 0,
+                Some(deadline - now),
+            );
+        }
+        true
+    }
+
+    /// Takes a permit if one is available, without blocking.
+    pub fn try_acquire(&self) -> bool {
+        let mut permits = self.permits.load(Ordering::Relaxed);
+        while permits != 0 {
+            match self.permits.compare_exchange_weak(
+                permits,
+                permits - 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return true,
+                Err(n) => permits = n,
+            }
+        }
+        false
+    }
+
+    /// Gives a permit back, waking up a waiter.
+    pub fn release(&self) {
+        self.permits.fetch_add(1, Ordering::Release);
+        super::ax_wait_queue_wake(&self.wq, 1);
+    }
+}
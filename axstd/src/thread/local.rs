@@ -0,0 +1,132 @@
+//! Thread-local storage, backed by the TLS area of each task.
+
+use core::cell::{Cell, RefCell};
+use core::fmt;
+
+/// A thread-local storage key which owns its contents.
+///
+/// Declared with [`thread_local!`]. The value is initialized lazily on the
+/// first access from each thread. Unlike `std`, it is not dropped when the
+/// thread exits.
+///
+/// [`thread_local!`]: crate::thread_local
+pub struct LocalKey<T: 'static> {
+    inner: fn() -> *const T,
+}
+
+impl<T: 'static> LocalKey<T> {
+    #[doc(hidden)]
+    pub const fn new(inner: fn() -> *const T) -> Self {
+        Self { inner }
+    }
+
+    /// Acquires a reference to the value of this key on the current thread.
+    pub fn with<F, R>(&'static self, f: F) -> R
+    where
+        F: FnOnce(&T) -> R,
+    {
+        f(unsafe { &*(self.inner)() })
+    }
+}
+
+impl<T: 'static> LocalKey<Cell<T>> {
+    /// Sets or initializes the contained value.
+    pub fn set(&'static self, value: T) {
+        self.with(|cell| cell.set(value))
+    }
+
+    /// Returns a copy of the contained value.
+    pub fn get(&'static self) -> T
+    where
+        T: Copy,
+    {
+        self.with(Cell::get)
+    }
+
+    /// Takes the contained value, leaving `Default::default()` in its place.
+    pub fn take(&'static self) -> T
+    where
+        T: Default,
+    {
+        self.with(Cell::take)
+    }
+
+    /// Replaces the contained value, returning the old value.
+    pub fn replace(&'static self, value: T) -> T {
+        self.with(|cell| cell.replace(value))
+    }
+}
+
+impl<T: 'static> LocalKey<RefCell<T>> {
+    /// Acquires a reference to the contained value.
+    pub fn with_borrow<F, R>(&'static self, f: F) -> R
+    where
+        F: FnOnce(&T) -> R,
+    {
+        self.with(|cell| f(&cell.borrow()))
+    }
+
+    /// Acquires a mutable reference to the contained value.
+    pub fn with_borrow_mut<F, R>(&'static self, f: F) -> R
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        self.with(|cell| f(&mut cell.borrow_mut()))
+    }
+
+    /// Sets or initializes the contained value.
+    pub fn set(&'static self, value: T) {
+        self.with_borrow_mut(|v| *v = value)
+    }
+
+    /// Takes the contained value, leaving `Default::default()` in its place.
+    pub fn take(&'static self) -> T
+    where
+        T: Default,
+    {
+        self.with(RefCell::take)
+    }
+
+    /// Replaces the contained value, returning the old value.
+    pub fn replace(&'static self, value: T) -> T {
+        self.with(|cell| cell.replace(value))
+    }
+}
+
+impl<T: 'static> fmt::Debug for LocalKey<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LocalKey").finish_non_exhaustive()
+    }
+}
+
+/// Declares thread-local statics of type [`LocalKey`], like `std`'s.
+///
+/// ```ignore
+/// thread_local! {
+///     static COUNTER: Cell<u32> = Cell::new(0);
+/// }
+///
+/// COUNTER.set(COUNTER.get() + 1);
+/// ```
+#[macro_export]
+#[allow_internal_unstable(thread_local)]
+macro_rules! thread_local {
+    () => {};
+
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty = $init:expr; $($rest:tt)*) => {
+        $crate::thread_local!($(#[$attr])* $vis static $name: $t = $init);
+        $crate::thread_local!($($rest)*);
+    };
+
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty = $init:expr) => {
+        $(#[$attr])*
+        $vis const $name: $crate::thread::LocalKey<$t> = {
+            fn __get() -> *const $t {
+                #[thread_local]
+                static VAL: ::core::cell::LazyCell<$t> = ::core::cell::LazyCell::new(|| $init);
+                ::core::cell::LazyCell::force(&VAL)
+            }
+            $crate::thread::LocalKey::new(__get)
+        };
+    };
+}
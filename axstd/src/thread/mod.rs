@@ -1,13 +1,22 @@
 use crate::String;
-use crate::io::{self, IoError, Result};
+use crate::io;
+use alloc::format;
 use alloc::sync::Arc;
 use core::cell::UnsafeCell;
 use core::num::NonZeroU64;
+use core::time::Duration;
+
+mod local;
+
+pub use axtask::Priority;
+pub use local::LocalKey;
+
+/// The result of joining a thread: the panic message if it panicked.
+pub type Result<T> = core::result::Result<T, String>;
 
 /// A handle to a task.
 pub struct AxTaskHandle {
     inner: axtask::AxTaskRef,
-    id: u64,
 }
 
 /// A unique identifier for a running thread.
@@ -15,14 +24,17 @@ pub struct AxTaskHandle {
 pub struct ThreadId(NonZeroU64);
 
 /// A handle to a thread.
+#[derive(Clone)]
 pub struct Thread {
     id: ThreadId,
+    task: axtask::AxTaskRef,
 }
 
 impl Thread {
-    fn from_id(id: u64) -> Self {
+    fn from_task(task: axtask::AxTaskRef) -> Self {
         Self {
-            id: ThreadId(NonZeroU64::new(id).unwrap()),
+            id: ThreadId(NonZeroU64::new(task.id().as_u64()).unwrap()),
+            task,
         }
     }
 
@@ -30,6 +42,36 @@ impl Thread {
     pub fn id(&self) -> ThreadId {
         self.id
     }
+
+    /// Gets the thread's name, `None` for unnamed threads.
+    pub fn name(&self) -> Option<&str> {
+        Some(self.task.name()).filter(|name| !name.is_empty())
+    }
+
+    /// Asks the thread to stop: it exits at its next yield, sleep or wait
+    /// for a lock. Returns `false` if it cannot be killed, or has finished.
+    pub fn kill(&self) -> bool {
+        axtask::kill(&self.task)
+    }
+
+    /// Moves the thread to the scheduling class and priority `prio`.
+    /// Returns `false` if `prio` is out of range, or a nice value the
+    /// scheduling policy does not support.
+    pub fn set_priority(&self, prio: Priority) -> bool {
+        axtask::set_priority(&self.task, prio)
+    }
+
+    /// Gets the real-time priority the thread runs at, including one lent
+    /// by the waiters of a priority-inheriting mutex; `0` for normal
+    /// threads.
+    pub fn rt_priority(&self) -> u8 {
+        self.task.rt_priority()
+    }
+}
+
+/// Gets a handle to the thread that invokes it.
+pub fn current() -> Thread {
+    Thread::from_task(axtask::current().as_task_ref().clone())
 }
 
 #[derive(Debug)]
@@ -47,7 +89,6 @@ impl Builder {
             stack_size: None,
         }
     }
-    /*
 
     /// Names the thread-to-be.
     pub fn name(mut self, name: String) -> Builder {
@@ -60,7 +101,6 @@ impl Builder {
         self.stack_size = Some(size);
         self
     }
-    */
 
     /// Spawns a new thread by taking ownership of the `Builder`, and returns an
     /// [`Result`] to its [`JoinHandle`].
@@ -78,7 +118,7 @@ impl Builder {
         unsafe { self.spawn_unchecked(f) }
     }
 
-    unsafe fn spawn_unchecked<F, T>(self, f: F) -> Result<JoinHandle<T>>
+    unsafe fn spawn_unchecked<F, T>(self, f: F) -> io::Result<JoinHandle<T>>
     where
         F: FnOnce() -> T + 'static,
         F: 'static,
@@ -100,11 +140,10 @@ impl Builder {
 
         let inner = axtask::spawn_raw(main, name, stack_size);
         let task = AxTaskHandle {
-            id: inner.id().as_u64(),
-            inner,
+            inner: inner.clone(),
         };
         Ok(JoinHandle {
-            thread: Thread::from_id(task.id),
+            thread: Thread::from_task(inner),
             native: task,
             packet: my_packet,
         })
@@ -128,6 +167,12 @@ pub fn yield_now() {
     axtask::yield_now();
 }
 
+/// Puts the current thread to sleep for at least the specified amount of
+/// time.
+pub fn sleep(dur: Duration) {
+    axtask::sleep(dur);
+}
+
 struct Packet<T> {
     result: UnsafeCell<Option<T>>,
 }
@@ -148,16 +193,33 @@ impl<T> JoinHandle<T> {
         &self.thread
     }
 
+    /// Waits for the thread to finish and returns its result, or the panic
+    /// message if it panicked.
+    ///
+    /// A panicking thread exits with [`axtask::PANIC_EXIT_CODE`] at once:
+    /// nothing is unwound, so the values it owns are leaked and the locks it
+    /// holds are never released. As joining cannot fail otherwise, a killed
+    /// joining thread exits.
     pub fn join(mut self) -> Result<T> {
-        Self::wait_for_exit(self.native).ok_or_else(|| IoError::BadState)?;
+        let exit_code =
+            Self::wait_for_exit(self.native).unwrap_or_else(|| crate::sync::exit_killed());
+        if exit_code == axtask::PANIC_EXIT_CODE {
+            return Err(self.thread.task.panic_message().unwrap_or_default());
+        }
         Arc::get_mut(&mut self.packet)
-            .unwrap()
-            .result
-            .get_mut()
-            .take()
-            .ok_or_else(|| IoError::BadState)
+            .and_then(|packet| packet.result.get_mut().take())
+            .ok_or_else(|| format!("thread exited with code {}", exit_code))
     }
 
+    /// Returns whether the thread has finished running its closure.
+    pub fn is_finished(&self) -> bool {
+        self.native.inner.state() == axtask::TaskState::Exited
+    }
+
+    /// Lets the thread run on its own: its result is dropped when it
+    /// finishes, and a panic is only logged.
+    pub fn detach(self) {}
+
     fn wait_for_exit(task: AxTaskHandle) -> Option<i32> {
         task.inner.join()
     }
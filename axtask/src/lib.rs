@@ -6,13 +6,21 @@ extern crate alloc;
 
 use crate::task::CurrentTask;
 use alloc::string::String;
+use axhal::time::TimeValue;
+use core::time::Duration;
 
+mod registry;
 mod run_queue;
 mod task;
+mod timers;
 mod wait_queue;
+mod watchdog;
 
-pub use task::{AxTaskRef, current};
-pub use wait_queue::WaitQueue;
+pub use registry::{TaskInfo, for_each_task};
+pub use run_queue::run_idle;
+pub use task::{AxTaskRef, KILL_EXIT_CODE, PANIC_EXIT_CODE, Priority, TaskId, TaskState, current};
+pub use wait_queue::{Interrupted, WaitQueue};
+pub use watchdog::{dump_tasks, report_deadlock};
 
 pub fn spawn_raw<F>(f: F, name: String, stack_size: usize) -> AxTaskRef
 where
@@ -23,21 +31,92 @@ where
     task
 }
 
+/// Spawns a task that runs in the user address space `aspace`.
+pub fn spawn_user<F>(f: F, name: String, stack_size: usize, aspace: axmm::AddrSpace) -> AxTaskRef
+where
+    F: FnOnce() + 'static,
+{
+    let task = task::Task::new_user(f, name, stack_size, aspace);
+    run_queue::RUN_QUEUE.lock().add_task(task.clone());
+    task
+}
+
 pub fn init_scheduler() {
     info!("Initialize scheduling...");
     run_queue::init();
 }
 
+pub fn init_scheduler_secondary() {
+    run_queue::init_secondary();
+}
+
 pub fn exit(exit_code: i32) -> ! {
     run_queue::RUN_QUEUE.lock().exit_current(exit_code)
 }
 
 pub fn yield_now() {
+    exit_if_killed();
     run_queue::RUN_QUEUE.lock().yield_current();
 }
 
+/// Asks `task` to stop. It exits with [`KILL_EXIT_CODE`] at its next
+/// yield or sleep, and its waits on wait queues return [`Interrupted`].
+/// Returns `false` if the task cannot be killed, or has exited.
+pub fn kill(task: &AxTaskRef) -> bool {
+    run_queue::RUN_QUEUE.lock().kill(task)
+}
+
+/// Exits the current task with [`KILL_EXIT_CODE`] if it was killed.
+pub fn exit_if_killed() {
+    if current().is_killed() {
+        exit(KILL_EXIT_CODE);
+    }
+}
+
+/// Sets the scheduling class and priority of `task`. Returns `false` if
+/// `prio` is out of range, or is a nice value the policy does not support.
+pub fn set_priority(task: &AxTaskRef, prio: Priority) -> bool {
+    run_queue::RUN_QUEUE.lock().set_priority(task, prio)
+}
+
+/// Records that the current task took a priority-inheriting mutex.
+pub fn pi_lock_acquired() {
+    current().pi_lock_acquired();
+}
+
+/// Lends the priority of the current task to `owner`, the holder of the
+/// priority-inheriting mutex it is about to wait for.
+pub fn inherit_priority(owner: &AxTaskRef) {
+    run_queue::RUN_QUEUE.lock().inherit_priority(owner);
+}
+
+/// Records that the current task released a priority-inheriting mutex. The
+/// inherited priority is dropped once it holds none.
+pub fn pi_lock_released() {
+    run_queue::RUN_QUEUE.lock().pi_lock_released();
+}
+
+/// Blocks the current task for at least `dur`.
+pub fn sleep(dur: Duration) {
+    sleep_until(axhal::time::current_time().saturating_add(dur));
+}
+
+/// Blocks the current task until `deadline` has passed.
+pub fn sleep_until(deadline: TimeValue) {
+    run_queue::RUN_QUEUE.lock().sleep_until(deadline);
+    exit_if_killed();
+}
+
+/// Handles the timer IRQ: checks for lockups, wakes up the expired
+/// sleepers, accounts the time-slice tick and programs the next deadline.
 pub fn on_timer_tick() {
-    run_queue::RUN_QUEUE.lock().scheduler_timer_tick();
+    timers::timer_fired();
+    let now_ns = axhal::time::current_time_nanos();
+    watchdog::check(now_ns, !current().is_idle());
+    let mut rq = run_queue::RUN_QUEUE.lock();
+    timers::check_events(&mut rq);
+    rq.scheduler_timer_tick();
+    timers::reprogram();
 }
 
 //
@@ -61,6 +140,29 @@ impl kernel_guard::KernelGuardIf for KernelGuardIfImpl {
     }
 }
 
+//
+// For panic
+//
+
+struct PanicHandlerImpl;
+
+#[crate_interface::impl_interface]
+impl axhal::PanicHandler for PanicHandlerImpl {
+    fn handle_panic(info: &core::panic::PanicInfo) {
+        let Some(curr) = current_may_uninit() else {
+            return;
+        };
+        // The main and idle tasks cannot go away, and a task in IRQ context
+        // or holding a spinlock cannot be switched out for good.
+        if curr.is_init() || curr.is_idle() || !axhal::irq::irqs_enabled() || !curr.can_preempt(0) {
+            return;
+        }
+        if curr.set_panic_message(alloc::format!("{}", info.message())) {
+            exit(PANIC_EXIT_CODE);
+        }
+    }
+}
+
 pub fn current_may_uninit() -> Option<CurrentTask> {
     CurrentTask::try_get()
 }
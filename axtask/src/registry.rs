@@ -0,0 +1,78 @@
+//! The registry of all tasks, for introspection.
+
+use crate::AxTaskRef;
+use crate::task::{AxTask, TaskId, TaskState};
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::time::Duration;
+use spinlock::SpinNoIrq;
+
+/// All tasks alive, by ID. Tasks unregister themselves when dropped.
+static REGISTRY: SpinNoIrq<BTreeMap<u64, Weak<AxTask>>> = SpinNoIrq::new(BTreeMap::new());
+
+pub(crate) fn register(task: &AxTaskRef) {
+    REGISTRY
+        .lock()
+        .insert(task.id().as_u64(), Arc::downgrade(task));
+}
+
+pub(crate) fn unregister(id: TaskId) {
+    REGISTRY.lock().remove(&id.as_u64());
+}
+
+/// A snapshot of the state and statistics of a task.
+#[derive(Debug, Clone)]
+pub struct TaskInfo {
+    pub id: TaskId,
+    pub name: String,
+    pub state: TaskState,
+    /// Time spent running.
+    pub run_time: Duration,
+    /// Number of times the task was switched out.
+    pub context_switches: u64,
+    /// Number of those switches that preempted it.
+    pub preemptions: u64,
+    /// Maximum kernel stack usage in bytes, `None` for boot contexts.
+    pub max_stack_usage: Option<usize>,
+    /// Kernel stack size in bytes, `None` for boot contexts.
+    pub stack_size: Option<usize>,
+    /// Address of the wait queue the task is blocked on, if any.
+    pub wait_queue: Option<usize>,
+    /// How long the task has been waiting on a wait queue, if it is.
+    pub wait_time: Option<Duration>,
+}
+
+impl TaskInfo {
+    fn new(task: &AxTaskRef) -> Self {
+        let (context_switches, preemptions) = task.switch_counts();
+        let stack = task.stack_usage();
+        Self {
+            id: task.id(),
+            name: task.name().into(),
+            state: task.state(),
+            run_time: task.run_time(),
+            context_switches,
+            preemptions,
+            max_stack_usage: stack.map(|(usage, _)| usage),
+            stack_size: stack.map(|(_, size)| size),
+            wait_queue: task.wait_queue_addr(),
+            wait_time: task.wait_time(),
+        }
+    }
+}
+
+/// Calls `f` with a snapshot of every task alive, in the order of their IDs.
+pub fn for_each_task(mut f: impl FnMut(&TaskInfo)) {
+    for task in &tasks() {
+        f(&TaskInfo::new(task));
+    }
+}
+
+/// Returns every task alive. The tasks are collected first: scanning them
+/// may be slow, and dropping the last reference to a task takes the
+/// registry lock.
+pub(crate) fn tasks() -> Vec<AxTaskRef> {
+    REGISTRY.lock().values().filter_map(Weak::upgrade).collect()
+}
@@ -1,30 +1,49 @@
 use crate::task::current;
-use crate::task::{CurrentTask, Task, TaskState};
-use crate::{AxTaskRef, WaitQueue};
+use crate::task::{AxScheduler, AxTask, CurrentTask, Priority, Task, TaskState};
+use crate::{AxTaskRef, WaitQueue, timers, watchdog};
 use alloc::collections::VecDeque;
 use alloc::sync::Arc;
+use axhal::time::TimeValue;
 use axsync::BootOnceCell;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use scheduler::{RtScheduler, Scheduler};
 use spinlock::SpinNoIrq;
 
 static EXITED_TASKS: SpinNoIrq<VecDeque<AxTaskRef>> = SpinNoIrq::new(VecDeque::new());
-static WAIT_FOR_EXIT: WaitQueue = WaitQueue::new();
+static WAIT_FOR_EXIT: WaitQueue = WaitQueue::new_idle();
+static GC_TASK: BootOnceCell<AxTaskRef> = BootOnceCell::new();
 
 pub(crate) static RUN_QUEUE: SpinNoIrq<AxRunQueue> = SpinNoIrq::new(AxRunQueue::new());
-static IDLE_TASK: BootOnceCell<AxTaskRef> = BootOnceCell::new();
+/// CPUs stalled in the idle task, to be kicked by an IPI when a task
+/// becomes ready.
+static IDLE_CPUS: AtomicUsize = AtomicUsize::new(0);
+const ALL_CPUS: usize = (1 << axconfig::SMP) - 1;
+
+percpu::def_percpu! {
+    static IDLE_TASK: BootOnceCell<AxTaskRef> = BootOnceCell::new();
+}
 
 pub(crate) struct AxRunQueue {
-    ready_queue: VecDeque<Arc<Task>>,
+    /// Real-time tasks, which always run before the normal ones.
+    rt: RtScheduler<AxTask>,
+    scheduler: AxScheduler,
 }
 
 impl AxRunQueue {
     pub const fn new() -> Self {
         Self {
-            ready_queue: VecDeque::new(),
+            rt: RtScheduler::new(),
+            scheduler: AxScheduler::new(),
         }
     }
     pub fn scheduler_timer_tick(&mut self) {
         let curr = current();
-        if !curr.is_idle() && curr.task_tick() {
+        // Real-time tasks are not preempted by ticks.
+        if !curr.is_idle()
+            && timers::tick_expired()
+            && curr.rt_priority() == 0
+            && self.scheduler.task_tick(curr.as_task_ref())
+        {
             curr.set_preempt_pending(true);
         }
     }
@@ -54,22 +73,92 @@ impl AxRunQueue {
     pub fn add_task(&mut self, task: AxTaskRef) {
         debug!("task spawn: {}", task.name());
         //assert!(task.is_ready());
-        self.ready_queue.push_back(task);
+        self.enqueue(task);
     }
 
-    pub fn pick_next_task(&mut self) -> Option<Arc<Task>> {
-        self.ready_queue.pop_front()
+    /// Puts a ready task into the queue of its class, and preempts the
+    /// current task if the new one has a higher real-time priority.
+    fn enqueue(&mut self, task: AxTaskRef) {
+        if let Some(curr) = CurrentTask::try_get()
+            && task.rt_priority() > curr.rt_priority()
+        {
+            curr.set_preempt_pending(true);
+        }
+        if task.rt_priority() > 0 {
+            self.rt.add_task(task);
+        } else {
+            self.scheduler.add_task(task);
+        }
+        kick_idle_cpu();
     }
 
-    pub fn put_prev_task(&mut self, prev: Arc<Task>, preempt: bool) {
-        if prev.time_slice() > 0 && preempt {
-            self.ready_queue.push_front(prev)
+    /// Changes the priority of `task` with `update`, moving it to the queue
+    /// of its new class if it is ready.
+    fn requeue_with(&mut self, task: &AxTaskRef, update: impl FnOnce()) {
+        let queued = if task.rt_priority() > 0 {
+            self.rt.remove_task(task)
         } else {
-            prev.reset_time_slice();
-            self.ready_queue.push_back(prev)
+            self.scheduler.remove_task(task)
+        };
+        update();
+        if let Some(task) = queued {
+            self.enqueue(task);
+        }
+    }
+
+    pub fn set_priority(&mut self, task: &AxTaskRef, prio: Priority) -> bool {
+        match prio {
+            Priority::Normal(nice) => {
+                // Only CFS has nice values, where `0` also resets them.
+                if !self.scheduler.set_priority(task, nice) && nice != 0 {
+                    return false;
+                }
+                self.requeue_with(task, || task.set_rt_priority(0));
+            }
+            Priority::RealTime(prio) => {
+                if !(1..=Priority::MAX_RT).contains(&prio) {
+                    return false;
+                }
+                self.requeue_with(task, || task.set_rt_priority(prio));
+            }
+        }
+        self.check_rt_preempt();
+        true
+    }
+
+    /// Raises the priority of `owner`, which holds a priority-inheriting
+    /// mutex, to the priority of the current task that waits for it.
+    pub fn inherit_priority(&mut self, owner: &AxTaskRef) {
+        let prio = current().rt_priority();
+        if prio > owner.rt_priority() {
+            debug!("task {} inherits priority {}", owner.name(), prio);
+            self.requeue_with(owner, || owner.set_inherited_priority(prio));
+        }
+    }
+
+    /// Drops the inherited priority of the current task once it holds no
+    /// priority-inheriting mutex.
+    pub fn pi_lock_released(&mut self) {
+        let curr = current();
+        if curr.pi_lock_released() {
+            curr.set_inherited_priority(0);
+            self.check_rt_preempt();
+        }
+    }
+
+    /// Preempts the current task if a ready task has a higher real-time
+    /// priority.
+    fn check_rt_preempt(&mut self) {
+        let curr = current();
+        if self.rt.highest_priority() > curr.rt_priority() {
+            curr.set_preempt_pending(true);
         }
     }
 
+    fn is_empty(&self) -> bool {
+        self.rt.is_empty() && self.scheduler.is_empty()
+    }
+
     pub fn yield_current(&mut self) {
         self.resched(false);
     }
@@ -77,26 +166,43 @@ impl AxRunQueue {
 
 impl AxRunQueue {
     fn resched(&mut self, preempt: bool) {
+        watchdog::touch(axhal::time::current_time_nanos());
         let prev = current();
         if prev.is_running() {
             prev.set_state(TaskState::Ready);
             if !prev.is_idle() {
-                self.put_prev_task(prev.clone(), preempt);
+                if prev.rt_priority() > 0 {
+                    self.rt.put_prev_task(prev.clone(), preempt);
+                } else {
+                    self.scheduler.put_prev_task(prev.clone(), preempt);
+                }
             }
         }
         let next = self
+            .rt
             .pick_next_task()
-            .unwrap_or_else(|| IDLE_TASK.get().clone());
-        self.switch_to(prev, next);
+            .or_else(|| self.scheduler.pick_next_task())
+            .unwrap_or_else(idle_task);
+        self.switch_to(prev, next, preempt);
     }
 
-    fn switch_to(&mut self, prev_task: CurrentTask, next_task: AxTaskRef) {
+    fn switch_to(&mut self, prev_task: CurrentTask, next_task: AxTaskRef, preempt: bool) {
         next_task.set_preempt_pending(false);
         next_task.set_state(TaskState::Running);
+        if next_task.is_idle() {
+            timers::stop_tick();
+        } else {
+            timers::start_tick();
+        }
+        timers::reprogram();
         if prev_task.ptr_eq(&next_task) {
             return;
         }
 
+        let now_ns = axhal::time::current_time_nanos();
+        prev_task.account_switch_out(now_ns, preempt);
+        next_task.account_switch_in(now_ns);
+
         unsafe {
             let prev_ctx_ptr = prev_task.ctx_mut_ptr();
             let next_ctx_ptr = next_task.ctx_mut_ptr();
@@ -104,6 +210,16 @@ impl AxRunQueue {
             assert!(Arc::strong_count(prev_task.as_task_ref()) > 1);
             assert!(Arc::strong_count(&next_task) >= 1);
 
+            let next_mm = next_task.mm_context();
+            let same_mm = match (prev_task.mm_context(), next_mm) {
+                (Some(prev), Some(next)) => Arc::ptr_eq(prev, next),
+                (prev, next) => prev.is_none() && next.is_none(),
+            };
+            if !same_mm {
+                axmm::switch_to(next_mm.map(|ctx| &**ctx));
+            }
+
+            axhal::trap::set_kernel_stack_bottom(next_task.kernel_stack_bottom());
             CurrentTask::set_current(prev_task, next_task);
             (*prev_ctx_ptr).switch_to(&*next_ctx_ptr);
         }
@@ -130,12 +246,19 @@ impl AxRunQueue {
         debug!("task unblock: {}", task.name());
         if task.is_blocked() {
             task.set_state(TaskState::Ready);
-            self.add_task(task); // TODO: priority
+            self.enqueue(task);
             if resched {
                 current().set_preempt_pending(true);
             }
         }
     }
+    /// Unblocks `task`, taken off a wait queue in its wait `epoch`, unless
+    /// it has ended that wait meanwhile.
+    pub fn unblock_waiter(&mut self, task: AxTaskRef, epoch: u64, resched: bool) {
+        if task.wait_epoch() == epoch {
+            self.unblock_task(task, resched);
+        }
+    }
     pub fn block_current<F>(&mut self, wait_queue_push: F)
     where
         F: FnOnce(AxTaskRef),
@@ -149,6 +272,34 @@ impl AxRunQueue {
         wait_queue_push(curr.clone());
         self.resched(false);
     }
+    pub fn sleep_until(&mut self, deadline: TimeValue) {
+        let curr = current();
+        debug!("task sleep: {}, deadline={:?}", curr.name(), deadline);
+        assert!(!curr.is_idle());
+        if !curr.is_killed() && axhal::time::current_time() < deadline {
+            self.block_current(|task| timers::set_alarm_wakeup(deadline, task));
+        }
+    }
+
+    /// Asks `task` to stop, waking it up if it is blocked. Returns `false`
+    /// for the tasks the kernel cannot do without, and exited ones.
+    pub fn kill(&mut self, task: &AxTaskRef) -> bool {
+        if task.is_idle()
+            || task.is_init()
+            || Arc::ptr_eq(task, GC_TASK.get())
+            || task.state() == TaskState::Exited
+        {
+            return false;
+        }
+        debug!("task kill: {}", task.name());
+        task.set_killed();
+        if task.is_blocked() {
+            // The wait functions take it off its wait queue.
+            timers::cancel_alarm(task);
+            self.unblock_task(task.clone(), true);
+        }
+        true
+    }
 }
 
 fn gc_entry() {
@@ -164,30 +315,74 @@ fn gc_entry() {
                 }
             }
         }
-        WAIT_FOR_EXIT.wait();
+        // The GC task cannot be killed.
+        let _ = WAIT_FOR_EXIT.wait();
     }
 }
 
 pub(crate) fn init() {
     const IDLE_TASK_STACK_SIZE: usize = 4096;
     let idle_task = Task::new(|| run_idle(), "idle".into(), IDLE_TASK_STACK_SIZE);
-    IDLE_TASK.init(idle_task.clone());
+    IDLE_TASK.with_current(|i| i.init(idle_task.clone()));
 
     let gc_task = Task::new(gc_entry, "gc".into(), axconfig::TASK_STACK_SIZE);
+    GC_TASK.init(gc_task.clone());
     RUN_QUEUE.lock().add_task(gc_task);
 
     let main_task = Task::new_init("main".into());
     main_task.set_state(TaskState::Running);
+    main_task.account_switch_in(axhal::time::current_time_nanos());
+    watchdog::touch(axhal::time::current_time_nanos());
+    timers::start_tick();
 
     unsafe { CurrentTask::init_current(main_task) }
 }
 
+pub(crate) fn init_secondary() {
+    // The boot context of a secondary CPU becomes its idle task.
+    let idle_task = Task::new_init("idle".into());
+    idle_task.set_state(TaskState::Running);
+    idle_task.account_switch_in(axhal::time::current_time_nanos());
+    watchdog::touch(axhal::time::current_time_nanos());
+    IDLE_TASK.with_current(|i| i.init(idle_task.clone()));
+
+    unsafe { CurrentTask::init_current(idle_task) }
+}
+
+fn idle_task() -> AxTaskRef {
+    IDLE_TASK.with_current(|i| i.get().clone())
+}
+
 pub fn yield_now() {
     RUN_QUEUE.lock().yield_current();
 }
 
+/// Wakes up an idle CPU other than the current one, if any.
+fn kick_idle_cpu() {
+    let idle = IDLE_CPUS.load(Ordering::SeqCst) & !(1 << axhal::cpu::this_cpu_id());
+    if idle != 0 {
+        let cpu_id = idle.trailing_zeros() as usize;
+        IDLE_CPUS.fetch_and(!(1 << cpu_id), Ordering::SeqCst);
+        axhal::mp::send_ipi(cpu_id);
+    }
+}
+
 pub fn run_idle() -> ! {
+    let cpu_bit = 1 << axhal::cpu::this_cpu_id();
     loop {
         yield_now();
+        // With IRQs disabled, a pending IRQ still ends the stall and is
+        // taken once they are enabled again. `add_task` sees the bit set
+        // here unless this check sees its task.
+        axhal::irq::disable_irqs();
+        let idle = IDLE_CPUS.fetch_or(cpu_bit, Ordering::SeqCst) | cpu_bit;
+        if RUN_QUEUE.lock().is_empty() {
+            if idle == ALL_CPUS && !crate::timers::has_sleepers() {
+                watchdog::check_idle();
+            }
+            axhal::irq::wait_for_irqs();
+        }
+        IDLE_CPUS.fetch_and(!cpu_bit, Ordering::SeqCst);
+        axhal::irq::enable_irqs();
     }
 }
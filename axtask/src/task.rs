@@ -1,16 +1,57 @@
 use crate::WaitQueue;
 use crate::run_queue::{AxRunQueue, RUN_QUEUE};
 use alloc::{boxed::Box, string::String, sync::Arc};
-use axconfig::{PAGE_SIZE, align_up};
 use axhal::TaskContext;
+use axhal::tls::TlsArea;
+use axmm::{AddrSpace, KernelStack, MmContext};
+use core::cell::UnsafeCell;
 use core::mem::ManuallyDrop;
 use core::ops::Deref;
-use core::sync::atomic::{
-    AtomicBool, AtomicI32, AtomicIsize, AtomicU8, AtomicU64, AtomicUsize, Ordering,
-};
-use core::{alloc::Layout, cell::UnsafeCell, ptr::NonNull};
+use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU8, AtomicU64, AtomicUsize, Ordering};
+use core::time::Duration;
+use spinlock::SpinNoIrq;
+
+// The scheduling policy, chosen by the `sched_*` features. The policy
+// wraps each task in its own task type.
+#[cfg(feature = "sched_cfs")]
+pub(crate) type AxTask = scheduler::CFSTask<Task>;
+#[cfg(feature = "sched_cfs")]
+pub(crate) type AxScheduler = scheduler::CFScheduler<Task>;
+
+#[cfg(all(feature = "sched_fifo", not(feature = "sched_cfs")))]
+pub(crate) type AxTask = scheduler::FifoTask<Task>;
+#[cfg(all(feature = "sched_fifo", not(feature = "sched_cfs")))]
+pub(crate) type AxScheduler = scheduler::FifoScheduler<Task>;
+
+#[cfg(not(any(feature = "sched_fifo", feature = "sched_cfs")))]
+const MAX_TIME_SLICE: usize = 5;
+#[cfg(not(any(feature = "sched_fifo", feature = "sched_cfs")))]
+pub(crate) type AxTask = scheduler::RRTask<Task, MAX_TIME_SLICE>;
+#[cfg(not(any(feature = "sched_fifo", feature = "sched_cfs")))]
+pub(crate) type AxScheduler = scheduler::RRScheduler<Task, MAX_TIME_SLICE>;
+
+pub type AxTaskRef = Arc<AxTask>;
+
+/// The exit code of a task that panicked.
+pub const PANIC_EXIT_CODE: i32 = 101;
+/// The exit code of a task that was killed.
+pub const KILL_EXIT_CODE: i32 = 137;
+
+/// The scheduling class and priority of a task.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Priority {
+    /// Scheduled by the normal policy, with a nice value in `-20..=19` that
+    /// only the CFS policy takes into account.
+    Normal(isize),
+    /// Real-time, in `1..=MAX_RT`: always runs before the normal tasks and
+    /// the real-time tasks of lower priorities, and is never preempted by
+    /// ticks.
+    RealTime(u8),
+}
 
-pub type AxTaskRef = Arc<Task>;
+impl Priority {
+    pub const MAX_RT: u8 = 99;
+}
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub struct TaskId(u64);
@@ -32,22 +73,56 @@ pub struct Task {
     is_init: bool,
     entry: Option<*mut dyn FnOnce()>,
     state: AtomicU8,
-    in_wait_queue: AtomicBool,
+    /// Address of the wait queue the task is in, `0` if none.
+    wait_queue: AtomicUsize,
+    /// Number of waits on wait queues ended, to tell stale queue entries.
+    wait_epoch: AtomicU64,
+    /// When the current wait on a wait queue began, in nanoseconds, `0` if
+    /// none.
+    wait_start_ns: AtomicU64,
+    /// Whether the watchdog is to report the current wait if it lasts: not
+    /// once reported, nor for waits on idle queues.
+    wait_watched: AtomicBool,
+    /// The deadline of the pending timer wakeup in nanoseconds, `0` if none.
+    timer_deadline: AtomicU64,
     need_resched: AtomicBool,
+    /// Set by `kill`, checked at the blocking and yielding points.
+    killed: AtomicBool,
+    /// The real-time priority set by `set_priority`, `0` for normal tasks.
+    rt_prio: AtomicU8,
+    /// The real-time priority inherited from the waiters of the
+    /// priority-inheriting mutexes held.
+    inherited_prio: AtomicU8,
+    /// Number of priority-inheriting mutexes held.
+    pi_locks: AtomicUsize,
+    /// Nanoseconds spent running, up to the last switch-out.
+    run_time_ns: AtomicU64,
+    /// When the task was last switched in.
+    last_run_ns: AtomicU64,
+    /// Number of times the task was switched out.
+    switches: AtomicU64,
+    /// Number of those switches that preempted it.
+    preemptions: AtomicU64,
     preempt_disable_count: AtomicUsize,
     exit_code: AtomicI32,
+    /// The panic message, if the task panicked.
+    panic_msg: SpinNoIrq<Option<String>>,
+    /// Joiners wait for the work of the task: the watchdog reports the task
+    /// itself if it hangs.
     wait_for_exit: WaitQueue,
-    kstack: Option<TaskStack>,
+    kstack: Option<KernelStack>,
     ctx: UnsafeCell<TaskContext>,
-    time_slice: AtomicIsize,
+    tls: TlsArea,
+    /// The user address space, `None` for kernel tasks.
+    aspace: Option<Arc<SpinNoIrq<AddrSpace>>>,
+    /// The address space context to run on, `None` for the kernel page table.
+    mm_context: Option<Arc<MmContext>>,
 }
 
 unsafe impl Send for Task {}
 unsafe impl Sync for Task {}
 
 impl Task {
-    const MAX_TIME_SLICE: isize = 5;
-
     fn new_common(id: TaskId, name: String) -> Self {
         Self {
             id,
@@ -56,32 +131,71 @@ impl Task {
             is_init: false,
             entry: None,
             state: AtomicU8::new(TaskState::Ready as u8),
-            in_wait_queue: AtomicBool::new(false),
+            wait_queue: AtomicUsize::new(0),
+            wait_epoch: AtomicU64::new(0),
+            wait_start_ns: AtomicU64::new(0),
+            wait_watched: AtomicBool::new(false),
+            timer_deadline: AtomicU64::new(0),
             need_resched: AtomicBool::new(false),
+            killed: AtomicBool::new(false),
+            rt_prio: AtomicU8::new(0),
+            inherited_prio: AtomicU8::new(0),
+            pi_locks: AtomicUsize::new(0),
+            run_time_ns: AtomicU64::new(0),
+            last_run_ns: AtomicU64::new(0),
+            switches: AtomicU64::new(0),
+            preemptions: AtomicU64::new(0),
             preempt_disable_count: AtomicUsize::new(0),
             exit_code: AtomicI32::new(0),
-            wait_for_exit: WaitQueue::new(),
+            panic_msg: SpinNoIrq::new(None),
+            wait_for_exit: WaitQueue::new_idle(),
             kstack: None,
             ctx: UnsafeCell::new(TaskContext::new()),
-            time_slice: AtomicIsize::new(Self::MAX_TIME_SLICE),
+            tls: TlsArea::alloc(),
+            aspace: None,
+            mm_context: None,
         }
     }
 
     /// Create a new task with the given entry function and stack size.
     pub(crate) fn new<F>(entry: F, name: String, stack_size: usize) -> AxTaskRef
+    where
+        F: FnOnce() + 'static,
+    {
+        Self::new_kernel(entry, name, stack_size).into_ref()
+    }
+
+    /// Create a new task running in the user address space `aspace`.
+    pub(crate) fn new_user<F>(
+        entry: F,
+        name: String,
+        stack_size: usize,
+        aspace: AddrSpace,
+    ) -> AxTaskRef
+    where
+        F: FnOnce() + 'static,
+    {
+        let mut t = Self::new_kernel(entry, name, stack_size);
+        t.mm_context = Some(aspace.context().clone());
+        t.aspace = Some(Arc::new(SpinNoIrq::new(aspace)));
+        t.into_ref()
+    }
+
+    fn new_kernel<F>(entry: F, name: String, stack_size: usize) -> Self
     where
         F: FnOnce() + 'static,
     {
         let mut t = Self::new_common(TaskId::new(), name);
         debug!("new task: {}", t.name());
-        let kstack = TaskStack::alloc(align_up(stack_size, PAGE_SIZE));
+        let kstack = KernelStack::alloc(stack_size);
         t.entry = Some(Box::into_raw(Box::new(entry)));
-        t.ctx.get_mut().init(task_entry as usize, kstack.top());
+        let tls = t.tls.tls_ptr();
+        t.ctx.get_mut().init(task_entry as usize, kstack.top(), tls);
         t.kstack = Some(kstack);
         if t.name == "idle" {
             t.is_idle = true;
         }
-        Arc::new(t)
+        t
     }
 
     pub(crate) fn new_init(name: String) -> AxTaskRef {
@@ -90,11 +204,17 @@ impl Task {
         if t.name == "idle" {
             t.is_idle = true;
         }
-        Arc::new(t)
+        t.into_ref()
+    }
+
+    fn into_ref(self) -> AxTaskRef {
+        let task = Arc::new(AxTask::new(self));
+        crate::registry::register(&task);
+        task
     }
 
     #[inline]
-    pub(crate) fn state(&self) -> TaskState {
+    pub fn state(&self) -> TaskState {
         self.state.load(Ordering::Acquire).into()
     }
 
@@ -120,13 +240,80 @@ impl Task {
     pub(crate) fn is_blocked(&self) -> bool {
         matches!(self.state(), TaskState::Blocked)
     }
+    /// Returns whether the task was asked to stop by `kill`.
+    #[inline]
+    pub fn is_killed(&self) -> bool {
+        self.killed.load(Ordering::Acquire)
+    }
+    #[inline]
+    pub(crate) fn set_killed(&self) {
+        self.killed.store(true, Ordering::Release);
+    }
     #[inline]
     pub(crate) fn in_wait_queue(&self) -> bool {
-        self.in_wait_queue.load(Ordering::Acquire)
+        self.wait_queue.load(Ordering::Acquire) != 0
     }
     #[inline]
-    pub(crate) fn set_in_wait_queue(&self, in_wait_queue: bool) {
-        self.in_wait_queue.store(in_wait_queue, Ordering::Release);
+    pub(crate) fn set_in_wait_queue(&self, wq: Option<&WaitQueue>) {
+        let addr = wq.map_or(0, |wq| wq as *const _ as usize);
+        self.wait_queue.store(addr, Ordering::Release);
+    }
+    #[inline]
+    pub(crate) fn wait_epoch(&self) -> u64 {
+        self.wait_epoch.load(Ordering::Acquire)
+    }
+    /// Records the start of a wait, unless it is a retry of the current one.
+    #[inline]
+    pub(crate) fn begin_wait(&self, watched: bool) {
+        let now_ns = axhal::time::current_time_nanos().max(1);
+        if self
+            .wait_start_ns
+            .compare_exchange(0, now_ns, Ordering::AcqRel, Ordering::Relaxed)
+            .is_ok()
+        {
+            self.wait_watched.store(watched, Ordering::Relaxed);
+        }
+    }
+    #[inline]
+    pub(crate) fn end_wait_epoch(&self) {
+        self.wait_start_ns.store(0, Ordering::Release);
+        self.wait_watched.store(false, Ordering::Relaxed);
+        self.wait_epoch.fetch_add(1, Ordering::AcqRel);
+    }
+    /// Returns how long the task has been waiting on a wait queue, `None`
+    /// if it is not waiting.
+    pub fn wait_time(&self) -> Option<Duration> {
+        match self.wait_start_ns.load(Ordering::Acquire) {
+            0 => None,
+            start_ns => {
+                let now_ns = axhal::time::current_time_nanos();
+                Some(Duration::from_nanos(now_ns.saturating_sub(start_ns)))
+            }
+        }
+    }
+    /// Returns whether the watchdog is to report the current wait, and
+    /// stops watching it.
+    pub(crate) fn take_wait_watched(&self) -> bool {
+        self.wait_watched.swap(false, Ordering::Relaxed)
+    }
+    /// Returns the address of the wait queue the task is in, if any.
+    pub fn wait_queue_addr(&self) -> Option<usize> {
+        match self.wait_queue.load(Ordering::Acquire) {
+            0 => None,
+            addr => Some(addr),
+        }
+    }
+    #[inline]
+    pub(crate) fn set_timer_deadline(&self, deadline_ns: u64) {
+        self.timer_deadline.store(deadline_ns, Ordering::Release);
+    }
+    #[inline]
+    pub(crate) fn take_timer_deadline(&self) -> u64 {
+        self.timer_deadline.swap(0, Ordering::AcqRel)
+    }
+    #[inline]
+    pub(crate) fn has_timer(&self) -> bool {
+        self.timer_deadline.load(Ordering::Acquire) != 0
     }
 
     pub(crate) fn notify_exit(&self, exit_code: i32, rq: &mut AxRunQueue) {
@@ -145,11 +332,110 @@ impl Task {
     pub fn name(&self) -> &str {
         self.name.as_str()
     }
+    /// Returns the top of the kernel stack, `None` for boot contexts.
+    pub fn kernel_stack_top(&self) -> Option<usize> {
+        self.kstack.as_ref().map(|s| s.top())
+    }
+    /// Returns whether `vaddr` is in the guard page below the kernel stack.
+    pub fn in_stack_guard(&self, vaddr: usize) -> bool {
+        self.kstack
+            .as_ref()
+            .is_some_and(|s| s.guard_contains(vaddr))
+    }
+    #[inline]
+    pub(crate) fn kernel_stack_bottom(&self) -> usize {
+        self.kstack.as_ref().map_or(0, |s| s.bottom())
+    }
+    /// Returns the user address space, `None` for kernel tasks.
+    pub fn aspace(&self) -> Option<&Arc<SpinNoIrq<AddrSpace>>> {
+        self.aspace.as_ref()
+    }
+    #[inline]
+    pub(crate) fn mm_context(&self) -> Option<&Arc<MmContext>> {
+        self.mm_context.as_ref()
+    }
+    /// Waits for the task to exit and returns its exit code, `None` if the
+    /// current task was killed meanwhile.
     pub fn join(&self) -> Option<i32> {
         self.wait_for_exit
-            .wait_until(|| self.state() == TaskState::Exited);
+            .wait_until(|| self.state() == TaskState::Exited)
+            .ok()?;
         Some(self.exit_code.load(Ordering::Acquire))
     }
+    /// Returns the panic message, `None` if the task has not panicked.
+    pub fn panic_message(&self) -> Option<String> {
+        self.panic_msg.lock().clone()
+    }
+    /// Records the panic message, returning `false` if the task was
+    /// already panicking.
+    pub(crate) fn set_panic_message(&self, msg: String) -> bool {
+        let mut panic_msg = self.panic_msg.lock();
+        if panic_msg.is_some() {
+            return false;
+        }
+        *panic_msg = Some(msg);
+        true
+    }
+    /// Returns the effective real-time priority, `0` for normal tasks.
+    pub fn rt_priority(&self) -> u8 {
+        self.rt_prio
+            .load(Ordering::Acquire)
+            .max(self.inherited_prio.load(Ordering::Acquire))
+    }
+    #[inline]
+    pub(crate) fn set_rt_priority(&self, prio: u8) {
+        self.rt_prio.store(prio, Ordering::Release);
+    }
+    #[inline]
+    pub(crate) fn set_inherited_priority(&self, prio: u8) {
+        self.inherited_prio.store(prio, Ordering::Release);
+    }
+    #[inline]
+    pub(crate) fn pi_lock_acquired(&self) {
+        self.pi_locks.fetch_add(1, Ordering::Relaxed);
+    }
+    /// Returns whether no priority-inheriting mutex is held any more.
+    #[inline]
+    pub(crate) fn pi_lock_released(&self) -> bool {
+        self.pi_locks.fetch_sub(1, Ordering::Relaxed) == 1
+    }
+    /// Accounts a switch to this task at `now_ns`.
+    pub(crate) fn account_switch_in(&self, now_ns: u64) {
+        self.last_run_ns.store(now_ns, Ordering::Relaxed);
+    }
+    /// Accounts a switch away from this task at `now_ns`.
+    pub(crate) fn account_switch_out(&self, now_ns: u64, preempt: bool) {
+        let ran = now_ns.saturating_sub(self.last_run_ns.load(Ordering::Relaxed));
+        self.run_time_ns.fetch_add(ran, Ordering::Relaxed);
+        self.switches.fetch_add(1, Ordering::Relaxed);
+        if preempt {
+            self.preemptions.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    /// Returns the time spent running, including the current run.
+    pub fn run_time(&self) -> Duration {
+        let mut ns = self.run_time_ns.load(Ordering::Relaxed);
+        if self.is_running() {
+            let now_ns = axhal::time::current_time_nanos();
+            ns += now_ns.saturating_sub(self.last_run_ns.load(Ordering::Relaxed));
+        }
+        Duration::from_nanos(ns)
+    }
+    /// Returns the number of times the task was switched out, and how many
+    /// of those preempted it.
+    pub fn switch_counts(&self) -> (u64, u64) {
+        (
+            self.switches.load(Ordering::Relaxed),
+            self.preemptions.load(Ordering::Relaxed),
+        )
+    }
+    /// Returns the maximum kernel stack usage and the stack size in bytes,
+    /// `None` for boot contexts.
+    pub fn stack_usage(&self) -> Option<(usize, usize)> {
+        self.kstack
+            .as_ref()
+            .map(|s| (s.max_usage(), s.top() - s.bottom()))
+    }
     pub(crate) fn set_preempt_pending(&self, pending: bool) {
         self.need_resched.store(pending, Ordering::Release)
     }
@@ -181,19 +467,17 @@ impl Task {
             }
         }
     }
+}
 
-    pub fn time_slice(&self) -> isize {
-        self.time_slice.load(Ordering::Acquire)
-    }
-
-    pub fn reset_time_slice(&self) {
-        self.time_slice
-            .store(Self::MAX_TIME_SLICE, Ordering::Release);
+impl Drop for Task {
+    fn drop(&mut self) {
+        crate::registry::unregister(self.id);
     }
+}
 
-    pub fn task_tick(&self) -> bool {
-        let old_slice = self.time_slice.fetch_sub(1, Ordering::Release);
-        old_slice <= 1
+impl scheduler::RtPriority for Task {
+    fn rt_priority(&self) -> u8 {
+        Task::rt_priority(self)
     }
 }
 
@@ -206,7 +490,7 @@ pub fn current() -> CurrentTask {
 /// The possible states of a task.
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
-pub(crate) enum TaskState {
+pub enum TaskState {
     Running = 1,
     Ready = 2,
     Blocked = 3,
@@ -226,34 +510,9 @@ impl From<u8> for TaskState {
     }
 }
 
-struct TaskStack {
-    ptr: NonNull<u8>,
-    layout: Layout,
-}
-
-impl TaskStack {
-    pub fn alloc(size: usize) -> Self {
-        let layout = Layout::from_size_align(size, 16).unwrap();
-        Self {
-            ptr: NonNull::new(unsafe { alloc::alloc::alloc(layout) }).unwrap(),
-            layout,
-        }
-    }
-
-    pub fn top(&self) -> usize {
-        self.ptr.as_ptr() as usize + self.layout.size()
-    }
-}
-
-impl Drop for TaskStack {
-    fn drop(&mut self) {
-        unsafe { alloc::alloc::dealloc(self.ptr.as_ptr(), self.layout) }
-    }
-}
-
 impl CurrentTask {
     pub(crate) fn try_get() -> Option<Self> {
-        let ptr: *const Task = axhal::cpu::current_task_ptr();
+        let ptr: *const AxTask = axhal::cpu::current_task_ptr();
         if !ptr.is_null() {
             Some(Self(unsafe { ManuallyDrop::new(AxTaskRef::from_raw(ptr)) }))
         } else {
@@ -278,6 +537,8 @@ impl CurrentTask {
     }
 
     pub(crate) unsafe fn init_current(init_task: AxTaskRef) {
+        // The boot context has no TLS area yet: it takes the one of its task.
+        unsafe { axhal::tls::write_thread_pointer(init_task.tls.tls_ptr()) };
         let ptr = Arc::into_raw(init_task);
         unsafe { axhal::cpu::set_current_task_ptr(ptr) };
     }
@@ -293,11 +554,13 @@ impl CurrentTask {
 impl Deref for CurrentTask {
     type Target = Task;
     fn deref(&self) -> &Self::Target {
-        self.0.deref()
+        self.0.inner()
     }
 }
 
 extern "C" fn task_entry() -> ! {
+    // The run queue lock is handed over from the previous task in `switch_to`.
+    unsafe { RUN_QUEUE.force_unlock() };
     axhal::irq::enable_irqs();
     let task = current();
     if let Some(entry) = task.entry {
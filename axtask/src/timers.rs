@@ -0,0 +1,123 @@
+//! Timer events that wake up sleeping tasks, and the time-slice tick.
+//!
+//! The timer of each CPU is programmed only for the nearest of its next
+//! tick and the earliest sleeper. The tick runs only while a task other
+//! than the idle task is running.
+
+use crate::AxTaskRef;
+use crate::run_queue::AxRunQueue;
+use crate::task::Task;
+use alloc::collections::BTreeMap;
+use axhal::time::TimeValue;
+use spinlock::SpinNoIrq;
+
+/// Sleeping tasks ordered by their deadlines (in nanoseconds). The task ID
+/// breaks ties between equal deadlines.
+static TIMER_LIST: SpinNoIrq<BTreeMap<(u64, u64), AxTaskRef>> = SpinNoIrq::new(BTreeMap::new());
+
+const TICK_INTERVAL_NANOS: u64 = axhal::time::NANOS_PER_SEC / axconfig::TICKS_PER_SEC as u64;
+
+percpu::def_percpu! {
+    /// The next time-slice tick of the CPU, `0` while the tick is stopped.
+    static TICK_DEADLINE: u64 = 0;
+    /// The deadline the timer of the CPU is programmed for, `0` if it has
+    /// fired, `u64::MAX` if disarmed.
+    static TIMER_DEADLINE: u64 = 0;
+}
+
+/// Wakes up `task` at `deadline` unless canceled before.
+pub(crate) fn set_alarm_wakeup(deadline: TimeValue, task: AxTaskRef) {
+    // `0` means no pending wakeup, and far deadlines saturate.
+    let deadline_ns = u64::try_from(deadline.as_nanos())
+        .unwrap_or(u64::MAX)
+        .max(1);
+    task.set_timer_deadline(deadline_ns);
+    TIMER_LIST
+        .lock()
+        .insert((deadline_ns, task.id().as_u64()), task);
+}
+
+/// Cancels the pending wakeup of `task`, if any.
+pub(crate) fn cancel_alarm(task: &Task) {
+    let deadline_ns = task.take_timer_deadline();
+    if deadline_ns != 0 {
+        TIMER_LIST.lock().remove(&(deadline_ns, task.id().as_u64()));
+    }
+}
+
+/// Returns whether some task waits for a timer wakeup.
+pub(crate) fn has_sleepers() -> bool {
+    !TIMER_LIST.lock().is_empty()
+}
+
+/// Wakes up the tasks whose deadlines have passed.
+pub(crate) fn check_events(rq: &mut AxRunQueue) {
+    let now_ns = axhal::time::current_time_nanos();
+    loop {
+        let task = {
+            let mut list = TIMER_LIST.lock();
+            match list.first_entry() {
+                Some(entry) if entry.key().0 <= now_ns => entry.remove(),
+                _ => break,
+            }
+        };
+        task.take_timer_deadline();
+        rq.unblock_task(task, true);
+    }
+}
+
+/// Starts the time-slice tick of the CPU, unless running.
+pub(crate) fn start_tick() {
+    if TICK_DEADLINE.read_current() == 0 {
+        TICK_DEADLINE.write_current(axhal::time::current_time_nanos() + TICK_INTERVAL_NANOS);
+        crate::watchdog::set_ticking(true);
+    }
+}
+
+/// Stops the time-slice tick of the CPU.
+pub(crate) fn stop_tick() {
+    if TICK_DEADLINE.read_current() != 0 {
+        TICK_DEADLINE.write_current(0);
+        crate::watchdog::set_ticking(false);
+    }
+}
+
+/// Returns whether the time-slice tick of the CPU has expired, and
+/// schedules the next one if so.
+pub(crate) fn tick_expired() -> bool {
+    let deadline = TICK_DEADLINE.read_current();
+    let now_ns = axhal::time::current_time_nanos();
+    if deadline == 0 || now_ns < deadline {
+        return false;
+    }
+    let mut next = deadline + TICK_INTERVAL_NANOS;
+    if next <= now_ns {
+        next = now_ns + TICK_INTERVAL_NANOS;
+    }
+    TICK_DEADLINE.write_current(next);
+    true
+}
+
+/// Marks the timer of the CPU as fired.
+pub(crate) fn timer_fired() {
+    TIMER_DEADLINE.write_current(0);
+}
+
+/// Programs the timer of the CPU for the nearest of its next tick and the
+/// earliest sleeper, or disarms it if there is neither.
+pub(crate) fn reprogram() {
+    let tick = match TICK_DEADLINE.read_current() {
+        0 => u64::MAX,
+        deadline => deadline,
+    };
+    let sleeper = TIMER_LIST
+        .lock()
+        .first_key_value()
+        .map_or(u64::MAX, |(key, _)| key.0);
+    let deadline = tick.min(sleeper);
+    if deadline != TIMER_DEADLINE.read_current() {
+        trace!("program timer: {}", deadline);
+        TIMER_DEADLINE.write_current(deadline);
+        axhal::time::set_oneshot_timer(deadline);
+    }
+}
@@ -2,85 +2,265 @@ use crate::run_queue::AxRunQueue;
 use crate::task::{CurrentTask, current};
 use crate::{AxTaskRef, run_queue::RUN_QUEUE};
 use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use core::cmp::Reverse;
+use core::sync::atomic::{AtomicUsize, Ordering, fence};
+use core::time::Duration;
 use spinlock::SpinRaw;
 
+/// The error of a wait cut short because the waiting task was killed.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Interrupted;
+
+/// A queue of blocked tasks waiting for an event.
+///
+/// Notifiers take the waiters off the queue without the `RUN_QUEUE` lock,
+/// which they only take to unblock the waiters taken, once per batch. A
+/// waiter queues itself before checking its condition, so a notifier
+/// either finds it or the waiter sees the condition set.
 pub struct WaitQueue {
-    queue: SpinRaw<VecDeque<AxTaskRef>>, // we already disabled IRQs when lock the `RUN_QUEUE`
+    /// The waiters, with the wait epoch they were queued in.
+    queue: SpinRaw<VecDeque<(AxTaskRef, u64)>>, // locked with IRQs disabled
+    /// The length of `queue`, to skip empty queues without locking.
+    len: AtomicUsize,
+    /// Whether the waiters wait for work rather than for a resource.
+    idle: bool,
 }
 
 impl WaitQueue {
     pub const fn new() -> Self {
         Self {
             queue: SpinRaw::new(VecDeque::new()),
+            len: AtomicUsize::new(0),
+            idle: false,
         }
     }
 
-    pub fn wait_until<F>(&self, condition: F)
+    /// Creates a queue of tasks idly waiting for work, which the watchdog
+    /// does not report however long they block.
+    pub const fn new_idle() -> Self {
+        Self {
+            queue: SpinRaw::new(VecDeque::new()),
+            len: AtomicUsize::new(0),
+            idle: true,
+        }
+    }
+
+    /// Queues the current task, unless it is still in. Called with
+    /// `RUN_QUEUE` locked, before checking the wait condition.
+    fn enqueue_current(&self, curr: &CurrentTask) {
+        if !curr.in_wait_queue() {
+            let mut queue = self.queue.lock();
+            curr.set_in_wait_queue(Some(self));
+            curr.begin_wait(!self.idle);
+            queue.push_back((curr.clone(), curr.wait_epoch()));
+            self.len.store(queue.len(), Ordering::Relaxed);
+        }
+        // Pairs with the fence of `has_waiters`.
+        fence(Ordering::SeqCst);
+    }
+
+    /// Checks for waiters without locking. A waiter that is not seen yet
+    /// sees the condition set before.
+    fn has_waiters(&self) -> bool {
+        fence(Ordering::SeqCst);
+        self.len.load(Ordering::Relaxed) != 0
+    }
+
+    pub fn wait_until<F>(&self, condition: F) -> Result<(), Interrupted>
     where
         F: Fn() -> bool,
     {
-        loop {
+        let curr = current();
+        let res = loop {
             let mut rq = RUN_QUEUE.lock();
+            self.enqueue_current(&curr);
             if condition() {
-                break;
+                break Ok(());
             }
-            rq.block_current(|task| {
-                task.set_in_wait_queue(true);
-                self.queue.lock().push_back(task);
-            });
-        }
-        self.cancel_events(current());
+            if curr.is_killed() {
+                break Err(Interrupted);
+            }
+            rq.block_current(|_| {});
+        };
+        self.cancel_events(curr, res.is_err());
+        res
     }
 
-    fn cancel_events(&self, curr: CurrentTask) {
-        if curr.in_wait_queue() {
+    /// Takes the current task off the queue if it is still in, and ends its
+    /// wait. A task that was notified but gives up passes the notification
+    /// on.
+    fn cancel_events(&self, curr: CurrentTask, give_up: bool) {
+        let notified = !curr.in_wait_queue() || {
             let _guard = kernel_guard::IrqSave::new();
-            self.queue.lock().retain(|t| !curr.ptr_eq(t));
-            curr.set_in_wait_queue(false);
+            let mut queue = self.queue.lock();
+            // A notifier may have taken it meanwhile.
+            let queued = curr.in_wait_queue();
+            if queued {
+                queue.retain(|(t, _)| !curr.ptr_eq(t));
+                self.len.store(queue.len(), Ordering::Relaxed);
+                curr.set_in_wait_queue(None);
+            }
+            !queued
+        };
+        // Notifiers still holding an entry of this wait leave the task be.
+        curr.end_wait_epoch();
+        if notified && give_up {
+            self.notify_one(true);
         }
     }
 
     pub(crate) fn notify_all_locked(&self, resched: bool, rq: &mut AxRunQueue) {
-        while let Some(task) = self.queue.lock().pop_front() {
-            task.set_in_wait_queue(false);
-            rq.unblock_task(task, resched);
+        for (task, epoch) in self.take_all() {
+            rq.unblock_waiter(task, epoch, resched);
         }
     }
     pub(crate) fn notify_one_locked(&self, resched: bool, rq: &mut AxRunQueue) -> bool {
-        if let Some(task) = self.queue.lock().pop_front() {
-            task.set_in_wait_queue(false);
-            rq.unblock_task(task, resched);
+        if let Some((task, epoch)) = self.pop_highest() {
+            rq.unblock_waiter(task, epoch, resched);
             true
         } else {
             false
         }
     }
-    pub fn wait(&self) {
-        RUN_QUEUE.lock().block_current(|task| {
-            task.set_in_wait_queue(true);
-            self.queue.lock().push_back(task);
-        });
-        self.cancel_events(current());
+    /// Takes the first of the waiters with the highest real-time priority.
+    fn pop_highest(&self) -> Option<(AxTaskRef, u64)> {
+        if !self.has_waiters() {
+            return None;
+        }
+        let _guard = kernel_guard::IrqSave::new();
+        let mut queue = self.queue.lock();
+        let idx = (0..queue.len()).min_by_key(|&i| Reverse(queue[i].0.rt_priority()))?;
+        let waiter = queue.remove(idx)?;
+        self.len.store(queue.len(), Ordering::Relaxed);
+        waiter.0.set_in_wait_queue(None);
+        Some(waiter)
+    }
+    /// Takes all the waiters.
+    fn take_all(&self) -> VecDeque<(AxTaskRef, u64)> {
+        if !self.has_waiters() {
+            return VecDeque::new();
+        }
+        let _guard = kernel_guard::IrqSave::new();
+        let waiters = core::mem::take(&mut *self.queue.lock());
+        self.len.store(0, Ordering::Relaxed);
+        for (task, _) in &waiters {
+            task.set_in_wait_queue(None);
+        }
+        waiters
+    }
+    pub fn wait(&self) -> Result<(), Interrupted> {
+        let curr = current();
+        {
+            let mut rq = RUN_QUEUE.lock();
+            if curr.is_killed() {
+                return Err(Interrupted);
+            }
+            self.enqueue_current(&curr);
+            rq.block_current(|_| {});
+        }
+        let res = if curr.is_killed() {
+            Err(Interrupted)
+        } else {
+            Ok(())
+        };
+        self.cancel_events(curr, res.is_err());
+        res
     }
+    /// Blocks the current task until notified or `dur` has passed. Returns
+    /// whether it timed out.
+    pub fn wait_timeout(&self, dur: Duration) -> Result<bool, Interrupted> {
+        let curr = current();
+        let deadline = axhal::time::current_time().saturating_add(dur);
+        {
+            let mut rq = RUN_QUEUE.lock();
+            if curr.is_killed() {
+                return Err(Interrupted);
+            }
+            self.enqueue_current(&curr);
+            rq.block_current(|task| crate::timers::set_alarm_wakeup(deadline, task));
+        }
+        // Notifiers take the task off the queue, the timer leaves it there.
+        let timeout = curr.in_wait_queue();
+        crate::timers::cancel_alarm(&curr);
+        let res = if curr.is_killed() {
+            Err(Interrupted)
+        } else {
+            Ok(timeout)
+        };
+        // A notifier may take it after the check above: as it reports a
+        // timeout, it passes the notification on.
+        self.cancel_events(curr, res != Ok(false));
+        res
+    }
+
+    /// Blocks the current task until `condition` becomes true or `dur` has
+    /// passed. Returns whether it timed out.
+    pub fn wait_timeout_until<F>(&self, dur: Duration, condition: F) -> Result<bool, Interrupted>
+    where
+        F: Fn() -> bool,
+    {
+        let curr = current();
+        let deadline = axhal::time::current_time().saturating_add(dur);
+        let res = loop {
+            let mut rq = RUN_QUEUE.lock();
+            self.enqueue_current(&curr);
+            if condition() {
+                break Ok(false);
+            }
+            if curr.is_killed() {
+                break Err(Interrupted);
+            }
+            if axhal::time::current_time() >= deadline {
+                break Ok(true);
+            }
+            rq.block_current(|task| crate::timers::set_alarm_wakeup(deadline, task));
+            crate::timers::cancel_alarm(&curr);
+        };
+        self.cancel_events(curr, res != Ok(false));
+        res
+    }
+
     pub fn notify_one(&self, resched: bool) -> bool {
-        let mut rq = RUN_QUEUE.lock();
-        if !self.queue.lock().is_empty() {
-            self.notify_one_locked(resched, &mut rq)
+        if let Some((task, epoch)) = self.pop_highest() {
+            RUN_QUEUE.lock().unblock_waiter(task, epoch, resched);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Wakes up `task` if it is in the queue. Returns whether it was.
+    pub fn notify_task(&self, resched: bool, task: &AxTaskRef) -> bool {
+        if !self.has_waiters() {
+            return false;
+        }
+        let waiter = {
+            let _guard = kernel_guard::IrqSave::new();
+            let mut queue = self.queue.lock();
+            let idx = queue.iter().position(|(t, _)| Arc::ptr_eq(t, task));
+            let waiter = idx.and_then(|idx| queue.remove(idx));
+            if let Some((task, _)) = &waiter {
+                self.len.store(queue.len(), Ordering::Relaxed);
+                task.set_in_wait_queue(None);
+            }
+            waiter
+        };
+        if let Some((task, epoch)) = waiter {
+            RUN_QUEUE.lock().unblock_waiter(task, epoch, resched);
+            true
         } else {
             false
         }
     }
 
     pub fn notify_all(&self, resched: bool) {
-        loop {
+        let waiters = self.take_all();
+        if !waiters.is_empty() {
             let mut rq = RUN_QUEUE.lock();
-            if let Some(task) = self.queue.lock().pop_front() {
-                task.set_in_wait_queue(false);
-                rq.unblock_task(task, resched);
-            } else {
-                break;
+            for (task, epoch) in waiters {
+                rq.unblock_waiter(task, epoch, resched);
             }
-            drop(rq); // we must unlock `RUN_QUEUE` after unlocking `self.queue`.
         }
     }
 }
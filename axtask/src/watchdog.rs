@@ -0,0 +1,189 @@
+//! A soft-lockup watchdog run on the timer ticks.
+//!
+//! Each CPU records when it last rescheduled and when it last took a tick.
+//! On its ticks a CPU reports itself if it has not rescheduled for
+//! `WATCHDOG_TICKS`, and reports the other CPUs whose tick is running but
+//! has not been taken for as long, as they are stuck with IRQs disabled.
+//!
+//! Tasks blocked with no timeout on a wait queue, other than an idle one,
+//! are reported once blocked for as long, or as soon as every CPU goes idle with no timer pending, as
+//! the ticks stop then and nothing is left to wake them up.
+
+use crate::AxTaskRef;
+use crate::registry::{for_each_task, tasks};
+use axconfig::{SMP, TICKS_PER_SEC, WATCHDOG_TICKS};
+use axhal::time::NANOS_PER_SEC;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use core::time::Duration;
+
+const TIMEOUT_NS: u64 = WATCHDOG_TICKS as u64 * NANOS_PER_SEC / TICKS_PER_SEC as u64;
+
+/// When the ticks last looked for blocked tasks, done at most once a
+/// second as every task is scanned.
+static LAST_SCAN_NS: AtomicU64 = AtomicU64::new(0);
+
+struct CpuWatch {
+    last_resched_ns: AtomicU64,
+    /// When the CPU last took a tick, `0` while its tick is stopped.
+    last_tick_ns: AtomicU64,
+    /// Whether the current lockup of the CPU was reported.
+    reported: AtomicBool,
+}
+
+impl CpuWatch {
+    const fn new() -> Self {
+        Self {
+            last_resched_ns: AtomicU64::new(0),
+            last_tick_ns: AtomicU64::new(0),
+            reported: AtomicBool::new(false),
+        }
+    }
+}
+
+static CPUS: [CpuWatch; SMP] = [const { CpuWatch::new() }; SMP];
+
+fn this_cpu() -> &'static CpuWatch {
+    &CPUS[axhal::cpu::this_cpu_id()]
+}
+
+/// Records that the current CPU rescheduled.
+pub(crate) fn touch(now_ns: u64) {
+    let cpu = this_cpu();
+    cpu.last_resched_ns.store(now_ns, Ordering::Relaxed);
+    cpu.reported.store(false, Ordering::Relaxed);
+}
+
+/// Records that the tick of the current CPU started or stopped.
+pub(crate) fn set_ticking(ticking: bool) {
+    let now_ns = if ticking {
+        axhal::time::current_time_nanos()
+    } else {
+        0
+    };
+    this_cpu().last_tick_ns.store(now_ns, Ordering::Relaxed);
+}
+
+/// Checks for lockups on a timer tick of the current CPU. It must not take
+/// the run queue lock, which may be the one held forever.
+pub(crate) fn check(now_ns: u64, busy: bool) {
+    let cpu_id = axhal::cpu::this_cpu_id();
+    let cpu = &CPUS[cpu_id];
+    if cpu.last_tick_ns.load(Ordering::Relaxed) != 0 {
+        cpu.last_tick_ns.store(now_ns, Ordering::Relaxed);
+    }
+
+    let last_resched = cpu.last_resched_ns.load(Ordering::Relaxed);
+    if busy
+        && now_ns.saturating_sub(last_resched) > TIMEOUT_NS
+        && !cpu.reported.swap(true, Ordering::Relaxed)
+    {
+        let curr = crate::current();
+        error!(
+            "watchdog: CPU {} stuck for {}ms in task {} ({}), which neither blocks nor yields",
+            cpu_id,
+            (now_ns - last_resched) / 1_000_000,
+            curr.id().as_u64(),
+            curr.name(),
+        );
+        dump_tasks();
+    }
+
+    for (id, other) in CPUS.iter().enumerate() {
+        let last_tick = other.last_tick_ns.load(Ordering::Relaxed);
+        if id != cpu_id
+            && last_tick != 0
+            && now_ns.saturating_sub(last_tick) > TIMEOUT_NS
+            && !other.reported.swap(true, Ordering::Relaxed)
+        {
+            error!(
+                "watchdog: CPU {} has taken no tick for {}ms, stuck with IRQs disabled",
+                id,
+                (now_ns - last_tick) / 1_000_000,
+            );
+            dump_tasks();
+        }
+    }
+
+    let last_scan = LAST_SCAN_NS.load(Ordering::Relaxed);
+    if now_ns.saturating_sub(last_scan) >= NANOS_PER_SEC
+        && LAST_SCAN_NS
+            .compare_exchange(last_scan, now_ns, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+    {
+        let timeout = Duration::from_nanos(TIMEOUT_NS);
+        if report_blocked(|task| task.wait_time().is_some_and(|t| t > timeout)) {
+            dump_tasks();
+        }
+    }
+}
+
+/// Checks for blocked tasks when every CPU is idle with no timer pending:
+/// only an external IRQ may wake them up now.
+pub(crate) fn check_idle() {
+    if report_blocked(|_| true) {
+        error!("watchdog: every CPU is idle with no timer pending");
+        dump_tasks();
+    }
+}
+
+/// Reports the tasks blocked on a wait queue with no timeout that `filter`
+/// selects, once per wait. Returns whether it reported any.
+fn report_blocked(filter: impl Fn(&AxTaskRef) -> bool) -> bool {
+    let mut reported = false;
+    for task in tasks() {
+        let Some(wq) = task.wait_queue_addr() else {
+            continue;
+        };
+        if task.is_blocked() && !task.has_timer() && filter(&task) && task.take_wait_watched() {
+            error!(
+                "watchdog: task {} ({}) blocked for {}ms on wait queue {:#x} with no timeout",
+                task.id().as_u64(),
+                task.name(),
+                task.wait_time().unwrap_or_default().as_millis(),
+                wq,
+            );
+            reported = true;
+        }
+    }
+    reported
+}
+
+/// Logs the state of every task, and the wait queue it is blocked on.
+pub fn dump_tasks() {
+    error!(
+        "{:>4} {:<16} {:<8} {:>10} {:>8} {:>8} {:>13} {:>10}  wait queue",
+        "ID", "NAME", "STATE", "TIME(ms)", "SWITCH", "PREEMPT", "STACK", "WAIT(ms)"
+    );
+    for_each_task(|t| {
+        let stack = match (t.max_stack_usage, t.stack_size) {
+            (Some(used), Some(size)) => alloc::format!("{}/{}", used, size),
+            _ => "-".into(),
+        };
+        let wq = t
+            .wait_queue
+            .map_or_else(|| "-".into(), |addr| alloc::format!("{:#x}", addr));
+        let wait = t
+            .wait_time
+            .map_or_else(|| "-".into(), |t| alloc::format!("{}", t.as_millis()));
+        error!(
+            "{:>4} {:<16} {:<8} {:>10} {:>8} {:>8} {:>13} {:>10}  {}",
+            t.id.as_u64(),
+            t.name,
+            alloc::format!("{:?}", t.state),
+            t.run_time.as_millis(),
+            t.context_switches,
+            t.preemptions,
+            stack,
+            wait,
+            wq,
+        );
+    });
+}
+
+/// Reports a deadlock detected by a synchronization primitive: logs the
+/// state of every task and panics.
+pub fn report_deadlock(msg: core::fmt::Arguments) -> ! {
+    error!("deadlock: {}", msg);
+    dump_tasks();
+    panic!("deadlock: {}", msg);
+}
@@ -0,0 +1,60 @@
+//! User applications: ELF loading and system calls.
+
+#![no_std]
+
+#[macro_use]
+extern crate log;
+extern crate alloc;
+
+mod loader;
+mod syscall;
+
+pub use loader::LoadError;
+pub use syscall::handle_syscall;
+
+use alloc::string::String;
+use axhal::TrapFrame;
+use axtask::AxTaskRef;
+use page_table::AccessType;
+
+/// Loads the ELF executable `elf_data` into a new address space and spawns
+/// a task that runs it in U mode.
+pub fn spawn(elf_data: &[u8], name: String) -> Result<AxTaskRef, LoadError> {
+    let (aspace, entry, ustack_top) = loader::load_elf(elf_data)?;
+    info!("spawn user app {}: entry {:#x}", name, entry);
+    let task = axtask::spawn_user(
+        move || {
+            let kstack_top = axtask::current().kernel_stack_top().unwrap();
+            let tf = TrapFrame::new_user(entry, ustack_top);
+            unsafe { tf.enter_uspace(kstack_top) }
+        },
+        name,
+        axconfig::TASK_STACK_SIZE,
+        aspace,
+    );
+    Ok(task)
+}
+
+/// Resolves a page fault in the address space of the current task.
+///
+/// An unresolved fault from user space terminates the current task.
+pub fn handle_page_fault(vaddr: usize, access: AccessType, is_user: bool) -> bool {
+    let curr = axtask::current();
+    if let Some(aspace) = curr.aspace()
+        && vaddr < axmm::user_space_end()
+        && aspace.lock().handle_page_fault(vaddr, access)
+    {
+        return true;
+    }
+    if is_user {
+        warn!(
+            "task {} ({}): segmentation fault @ {:#x}, access={:?}",
+            curr.id().as_u64(),
+            curr.name(),
+            vaddr,
+            access
+        );
+        axtask::exit(-1);
+    }
+    false
+}
@@ -0,0 +1,62 @@
+use axconfig::{USER_STACK_SIZE, USER_STACK_TOP};
+use axmm::{AddrSpace, MmError};
+use elf_parser::{EM_RISCV, ElfError, ElfFile, PF_W, PF_X};
+use page_table::MappingFlags;
+
+#[derive(Debug)]
+pub enum LoadError {
+    /// The file is not a valid ELF executable.
+    BadElf(ElfError),
+    /// The file is not built for this architecture.
+    BadMachine(u16),
+    /// A segment does not fit in user space.
+    BadSegment,
+}
+
+impl From<ElfError> for LoadError {
+    fn from(err: ElfError) -> Self {
+        Self::BadElf(err)
+    }
+}
+
+impl From<MmError> for LoadError {
+    fn from(_: MmError) -> Self {
+        Self::BadSegment
+    }
+}
+
+/// Loads all `PT_LOAD` segments of `elf_data` into a new address space and
+/// maps the user stack. Returns the space, the entry and the stack top.
+pub(crate) fn load_elf(elf_data: &[u8]) -> Result<(AddrSpace, usize, usize), LoadError> {
+    let elf = ElfFile::parse(elf_data)?;
+    if elf.machine() != EM_RISCV {
+        return Err(LoadError::BadMachine(elf.machine()));
+    }
+
+    let mut aspace = AddrSpace::new_user();
+    for ph in elf.program_headers().filter(|ph| ph.is_load()) {
+        if ph.p_filesz > ph.p_memsz {
+            return Err(LoadError::BadSegment);
+        }
+        let mut flags = MappingFlags::READ | MappingFlags::USER;
+        if ph.p_flags & PF_W != 0 {
+            flags |= MappingFlags::WRITE;
+        }
+        if ph.p_flags & PF_X != 0 {
+            flags |= MappingFlags::EXECUTE;
+        }
+        debug!(
+            "load segment [{:#x}, {:#x}) flags {:?}",
+            ph.p_vaddr,
+            ph.p_vaddr + ph.p_memsz,
+            flags
+        );
+        aspace.map_alloc(ph.p_vaddr, ph.p_memsz, flags)?;
+        // The rest up to `p_memsz` (.bss) is already zeroed.
+        aspace.write(ph.p_vaddr, elf.segment_data(&ph)?)?;
+    }
+
+    let flags = MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER;
+    aspace.map_lazy(USER_STACK_TOP - USER_STACK_SIZE, USER_STACK_SIZE, flags)?;
+    Ok((aspace, elf.entry(), USER_STACK_TOP))
+}
@@ -0,0 +1,50 @@
+use page_table::MappingFlags;
+
+const SYS_WRITE: usize = 64;
+const SYS_EXIT: usize = 93;
+const SYS_SCHED_YIELD: usize = 124;
+const SYS_GETPID: usize = 172;
+
+const EBADF: isize = 9;
+const EFAULT: isize = 14;
+const ENOSYS: isize = 38;
+
+/// Dispatches a system call from user space. Errors are returned as
+/// negated errno values.
+pub fn handle_syscall(syscall_num: usize, args: [usize; 6]) -> isize {
+    trace!("syscall {} {:#x?}", syscall_num, args);
+    match syscall_num {
+        SYS_WRITE => sys_write(args[0], args[1], args[2]),
+        SYS_EXIT => axtask::exit(args[0] as i32),
+        SYS_SCHED_YIELD => {
+            axtask::yield_now();
+            0
+        }
+        SYS_GETPID => axtask::current().id().as_u64() as isize,
+        _ => {
+            warn!("unsupported syscall {}", syscall_num);
+            -ENOSYS
+        }
+    }
+}
+
+fn sys_write(fd: usize, buf: usize, len: usize) -> isize {
+    if fd != 1 && fd != 2 {
+        return -EBADF;
+    }
+    let curr = axtask::current();
+    let Some(aspace) = curr.aspace() else {
+        return -EFAULT;
+    };
+    if !aspace
+        .lock()
+        .check_region(buf, len, MappingFlags::READ | MappingFlags::USER)
+    {
+        return -EFAULT;
+    }
+    axhal::uaccess::with_user_access(|| {
+        let bytes = unsafe { core::slice::from_raw_parts(buf as *const u8, len) };
+        axhal::console::write_bytes(bytes);
+    });
+    len as isize
+}
@@ -0,0 +1,143 @@
+#![no_std]
+//! A minimal parser for little-endian ELF64 executables.
+
+const ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+const EHDR_SIZE: usize = 64;
+const PHDR_SIZE: usize = 56;
+
+pub const ET_EXEC: u16 = 2;
+pub const ET_DYN: u16 = 3;
+pub const EM_RISCV: u16 = 243;
+
+pub const PT_LOAD: u32 = 1;
+
+pub const PF_X: u32 = 1 << 0;
+pub const PF_W: u32 = 1 << 1;
+pub const PF_R: u32 = 1 << 2;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ElfError {
+    TooShort,
+    BadMagic,
+    NotElf64,
+    NotLittleEndian,
+    NotExecutable,
+    BadProgramHeader,
+}
+
+pub type ElfResult<T = ()> = Result<T, ElfError>;
+
+/// A program header (segment) of an ELF file.
+#[derive(Debug, Clone, Copy)]
+pub struct ProgramHeader {
+    pub p_type: u32,
+    pub p_flags: u32,
+    pub p_offset: usize,
+    pub p_vaddr: usize,
+    pub p_filesz: usize,
+    pub p_memsz: usize,
+    pub p_align: usize,
+}
+
+impl ProgramHeader {
+    pub fn is_load(&self) -> bool {
+        self.p_type == PT_LOAD
+    }
+}
+
+pub struct ElfFile<'a> {
+    data: &'a [u8],
+    e_type: u16,
+    e_machine: u16,
+    e_entry: usize,
+    e_phoff: usize,
+    e_phentsize: usize,
+    e_phnum: usize,
+}
+
+impl<'a> ElfFile<'a> {
+    /// Parses the ELF header and checks the program header table.
+    pub fn parse(data: &'a [u8]) -> ElfResult<Self> {
+        if data.len() < EHDR_SIZE {
+            return Err(ElfError::TooShort);
+        }
+        if data[0..4] != ELF_MAGIC {
+            return Err(ElfError::BadMagic);
+        }
+        if data[4] != ELFCLASS64 {
+            return Err(ElfError::NotElf64);
+        }
+        if data[5] != ELFDATA2LSB {
+            return Err(ElfError::NotLittleEndian);
+        }
+        let elf = Self {
+            data,
+            e_type: read_u16(data, 16),
+            e_machine: read_u16(data, 18),
+            e_entry: read_u64(data, 24) as usize,
+            e_phoff: read_u64(data, 32) as usize,
+            e_phentsize: read_u16(data, 54) as usize,
+            e_phnum: read_u16(data, 56) as usize,
+        };
+        if elf.e_type != ET_EXEC && elf.e_type != ET_DYN {
+            return Err(ElfError::NotExecutable);
+        }
+        if elf.e_phnum > 0 {
+            let table_size = elf.e_phentsize.checked_mul(elf.e_phnum);
+            let table_end = table_size.and_then(|size| size.checked_add(elf.e_phoff));
+            if elf.e_phentsize < PHDR_SIZE || table_end.is_none_or(|end| end > data.len()) {
+                return Err(ElfError::BadProgramHeader);
+            }
+        }
+        Ok(elf)
+    }
+
+    pub fn machine(&self) -> u16 {
+        self.e_machine
+    }
+
+    pub fn entry(&self) -> usize {
+        self.e_entry
+    }
+
+    pub fn program_headers(&self) -> impl Iterator<Item = ProgramHeader> + '_ {
+        (0..self.e_phnum).map(move |i| {
+            let off = self.e_phoff + i * self.e_phentsize;
+            ProgramHeader {
+                p_type: read_u32(self.data, off),
+                p_flags: read_u32(self.data, off + 4),
+                p_offset: read_u64(self.data, off + 8) as usize,
+                p_vaddr: read_u64(self.data, off + 16) as usize,
+                p_filesz: read_u64(self.data, off + 32) as usize,
+                p_memsz: read_u64(self.data, off + 40) as usize,
+                p_align: read_u64(self.data, off + 48) as usize,
+            }
+        })
+    }
+
+    /// Returns the file content of the given segment.
+    pub fn segment_data(&self, ph: &ProgramHeader) -> ElfResult<&'a [u8]> {
+        let end = ph
+            .p_offset
+            .checked_add(ph.p_filesz)
+            .ok_or(ElfError::BadProgramHeader)?;
+        if ph.p_filesz > ph.p_memsz || end > self.data.len() {
+            return Err(ElfError::BadProgramHeader);
+        }
+        Ok(&self.data[ph.p_offset..end])
+    }
+}
+
+fn read_u16(data: &[u8], off: usize) -> u16 {
+    u16::from_le_bytes([data[off], data[off + 1]])
+}
+
+fn read_u32(data: &[u8], off: usize) -> u32 {
+    u32::from_le_bytes(data[off..off + 4].try_into().unwrap())
+}
+
+fn read_u64(data: &[u8], off: usize) -> u64 {
+    u64::from_le_bytes(data[off..off + 8].try_into().unwrap())
+}
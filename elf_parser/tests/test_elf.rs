@@ -0,0 +1,73 @@
+use elf_parser::{EM_RISCV, ET_EXEC, ElfError, ElfFile, PF_R, PF_X, PT_LOAD};
+
+const ENTRY: u64 = 0x1000;
+const CODE: [u8; 8] = [0x93, 0x08, 0xd0, 0x05, 0x73, 0x00, 0x00, 0x00]; // li a7, 93; ecall
+
+fn build_elf() -> Vec<u8> {
+    let mut elf = vec![0u8; 64 + 56];
+    elf[0..4].copy_from_slice(b"\x7fELF");
+    elf[4] = 2; // ELFCLASS64
+    elf[5] = 1; // ELFDATA2LSB
+    elf[6] = 1; // EV_CURRENT
+    elf[16..18].copy_from_slice(&ET_EXEC.to_le_bytes());
+    elf[18..20].copy_from_slice(&EM_RISCV.to_le_bytes());
+    elf[24..32].copy_from_slice(&ENTRY.to_le_bytes());
+    elf[32..40].copy_from_slice(&64u64.to_le_bytes()); // e_phoff
+    elf[52..54].copy_from_slice(&64u16.to_le_bytes()); // e_ehsize
+    elf[54..56].copy_from_slice(&56u16.to_le_bytes()); // e_phentsize
+    elf[56..58].copy_from_slice(&1u16.to_le_bytes()); // e_phnum
+
+    let ph = &mut elf[64..];
+    ph[0..4].copy_from_slice(&PT_LOAD.to_le_bytes());
+    ph[4..8].copy_from_slice(&(PF_R | PF_X).to_le_bytes());
+    ph[8..16].copy_from_slice(&120u64.to_le_bytes()); // p_offset
+    ph[16..24].copy_from_slice(&ENTRY.to_le_bytes()); // p_vaddr
+    ph[32..40].copy_from_slice(&(CODE.len() as u64).to_le_bytes()); // p_filesz
+    ph[40..48].copy_from_slice(&0x2000u64.to_le_bytes()); // p_memsz
+    ph[48..56].copy_from_slice(&0x1000u64.to_le_bytes()); // p_align
+    elf.extend_from_slice(&CODE);
+    elf
+}
+
+#[test]
+fn test_parse() {
+    let data = build_elf();
+    let elf = ElfFile::parse(&data).unwrap();
+    assert_eq!(elf.machine(), EM_RISCV);
+    assert_eq!(elf.entry(), ENTRY as usize);
+
+    let phs: Vec<_> = elf.program_headers().collect();
+    assert_eq!(phs.len(), 1);
+    assert!(phs[0].is_load());
+    assert_eq!(phs[0].p_flags, PF_R | PF_X);
+    assert_eq!(phs[0].p_vaddr, ENTRY as usize);
+    assert_eq!(phs[0].p_memsz, 0x2000);
+    assert_eq!(elf.segment_data(&phs[0]).unwrap(), &CODE);
+}
+
+#[test]
+fn test_bad_elf() {
+    let data = build_elf();
+    assert_eq!(ElfFile::parse(&data[..32]).err(), Some(ElfError::TooShort));
+
+    let mut bad = data.clone();
+    bad[0] = 0;
+    assert_eq!(ElfFile::parse(&bad).err(), Some(ElfError::BadMagic));
+
+    let mut bad = data.clone();
+    bad[4] = 1;
+    assert_eq!(ElfFile::parse(&bad).err(), Some(ElfError::NotElf64));
+
+    let mut bad = data.clone();
+    bad[56] = 3; // e_phnum beyond the file
+    assert_eq!(ElfFile::parse(&bad).err(), Some(ElfError::BadProgramHeader));
+
+    let mut bad = data.clone();
+    bad[64 + 32] = 0xff; // p_filesz beyond the file
+    let elf = ElfFile::parse(&bad).unwrap();
+    let ph = elf.program_headers().next().unwrap();
+    assert_eq!(
+        elf.segment_data(&ph).err(),
+        Some(ElfError::BadProgramHeader)
+    );
+}
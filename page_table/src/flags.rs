@@ -0,0 +1,100 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+
+bitflags::bitflags! {
+    /// Architecture-neutral permissions and memory type of a mapping.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct MappingFlags: usize {
+        const READ = 1 << 0;
+        const WRITE = 1 << 1;
+        const EXECUTE = 1 << 2;
+        /// Accessible from U-mode.
+        const USER = 1 << 3;
+        /// Device memory (MMIO): non-cacheable, strongly ordered.
+        const DEVICE = 1 << 4;
+        /// Normal memory, non-cacheable.
+        const UNCACHED = 1 << 5;
+    }
+}
+
+pub(crate) const _PAGE_V: usize = 1 << 0; /* Valid */
+pub(crate) const _PAGE_R: usize = 1 << 1; /* Readable */
+pub(crate) const _PAGE_W: usize = 1 << 2; /* Writable */
+pub(crate) const _PAGE_E: usize = 1 << 3; /* Executable */
+pub(crate) const _PAGE_U: usize = 1 << 4; /* User */
+pub(crate) const _PAGE_G: usize = 1 << 5; /* Global */
+pub(crate) const _PAGE_A: usize = 1 << 6; /* Accessed (set by hardware) */
+pub(crate) const _PAGE_D: usize = 1 << 7; /* Dirty (set by hardware)*/
+pub(crate) const _PAGE_LAZY: usize = 1 << 8; /* Software: backed on first touch */
+
+/* Svpbmt memory types */
+pub(crate) const _PAGE_PBMT_NC: usize = 1 << 61; /* Non-cacheable, idempotent */
+pub(crate) const _PAGE_PBMT_IO: usize = 2 << 61; /* Non-cacheable, I/O */
+pub(crate) const _PAGE_PBMT_MASK: usize = 3 << 61;
+
+/// Whether the harts implement Svpbmt. Without it the PBMT bits are
+/// reserved, and the memory type comes from the PMAs alone.
+static SVPBMT: AtomicBool = AtomicBool::new(false);
+
+/// Enables the Svpbmt memory types for the mappings made from now on.
+pub fn enable_svpbmt() {
+    SVPBMT.store(true, Ordering::Relaxed);
+}
+
+impl MappingFlags {
+    /// Kernel code.
+    pub const KERNEL_RX: Self = Self::READ.union(Self::EXECUTE);
+    /// Kernel data.
+    pub const KERNEL_RW: Self = Self::READ.union(Self::WRITE);
+    /// Kernel memory of any use, as in the boot mappings.
+    pub const KERNEL_RWX: Self = Self::KERNEL_RW.union(Self::EXECUTE);
+
+    /// Converts to the bits of a valid leaf PTE.
+    pub fn to_pte_bits(self) -> usize {
+        let mut bits = _PAGE_V | _PAGE_A | _PAGE_D;
+        if self.contains(Self::READ) {
+            bits |= _PAGE_R;
+        }
+        if self.contains(Self::WRITE) {
+            bits |= _PAGE_W;
+        }
+        if self.contains(Self::EXECUTE) {
+            bits |= _PAGE_E;
+        }
+        if self.contains(Self::USER) {
+            bits |= _PAGE_U;
+        } else {
+            bits |= _PAGE_G;
+        }
+        if SVPBMT.load(Ordering::Relaxed) {
+            if self.contains(Self::DEVICE) {
+                bits |= _PAGE_PBMT_IO;
+            } else if self.contains(Self::UNCACHED) {
+                bits |= _PAGE_PBMT_NC;
+            }
+        }
+        bits
+    }
+
+    /// Converts from the bits of a leaf PTE.
+    pub fn from_pte_bits(bits: usize) -> Self {
+        let mut flags = Self::empty();
+        if bits & _PAGE_R != 0 {
+            flags |= Self::READ;
+        }
+        if bits & _PAGE_W != 0 {
+            flags |= Self::WRITE;
+        }
+        if bits & _PAGE_E != 0 {
+            flags |= Self::EXECUTE;
+        }
+        if bits & _PAGE_U != 0 {
+            flags |= Self::USER;
+        }
+        match bits & _PAGE_PBMT_MASK {
+            _PAGE_PBMT_IO => flags |= Self::DEVICE,
+            _PAGE_PBMT_NC => flags |= Self::UNCACHED,
+            _ => {}
+        }
+        flags
+    }
+}
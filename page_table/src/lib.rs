@@ -1,44 +1,87 @@
 #![no_std]
 /*
  * RiscV64 PTE format:
- * | XLEN-1  10 | 9             8 | 7 | 6 | 5 | 4 | 3 | 2 | 1 | 0
- *       PFN      reserved for SW   D   A   G   U   X   W   R   V
+ * | 63 | 62  61 | 60  54 | 53  10 | 9             8 | 7 | 6 | 5 | 4 | 3 | 2 | 1 | 0
+ *   N    PBMT     rsvd      PFN     reserved for SW   D   A   G   U   X   W   R   V
  */
 use axconfig::{ASPACE_BITS, PAGE_SHIFT, PAGE_SIZE};
 use axconfig::{align_down, align_offset, is_aligned};
 use axconfig::{pfn_phys, phys_pfn, phys_to_virt, virt_to_phys};
 use core::alloc::Layout;
 use core::cmp::min;
+use core::ops::Range;
+use core::sync::atomic::{AtomicUsize, Ordering};
 extern crate alloc;
 
-const _PAGE_V: usize = 1 << 0; /* Valid */
-const _PAGE_R: usize = 1 << 1; /* Readable */
-const _PAGE_W: usize = 1 << 2; /* Writable */
-const _PAGE_E: usize = 1 << 3; /* Executable */
-const _PAGE_U: usize = 1 << 4; /* User */
-const _PAGE_G: usize = 1 << 5; /* Global */
-const _PAGE_A: usize = 1 << 6; /* Accessed (set by hardware) */
-const _PAGE_D: usize = 1 << 7; /* Dirty (set by hardware)*/
+mod flags;
+mod owned;
+
+use flags::{_PAGE_E, _PAGE_LAZY, _PAGE_R, _PAGE_V, _PAGE_W};
+
+pub use flags::{MappingFlags, enable_svpbmt};
+pub use owned::OwnedPageTable;
 
 const PAGE_TABLE: usize = _PAGE_V;
-pub const PAGE_KERNEL_RO: usize = _PAGE_V | _PAGE_R | _PAGE_G | _PAGE_A | _PAGE_D;
-pub const PAGE_KERNEL_RW: usize = PAGE_KERNEL_RO | _PAGE_W;
-pub const PAGE_KERNEL_RX: usize = PAGE_KERNEL_RO | _PAGE_E;
-pub const PAGE_KERNEL_RWX: usize = PAGE_KERNEL_RW | _PAGE_E;
 
-#[derive(Debug)]
-pub enum PagingError {}
+/// The kind of memory access that caused a page fault.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum AccessType {
+    Read,
+    Write,
+    Execute,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum PagingError {
+    /// Failed to allocate an intermediate page table.
+    NoMemory,
+    /// The address is not mapped.
+    NotMapped,
+    /// The address is already mapped.
+    AlreadyMapped,
+    /// The range covers only a part of a huge page.
+    MappedToHugePage,
+}
 pub type PagingResult<T = ()> = Result<T, PagingError>;
 const PAGE_PFN_SHIFT: usize = 10;
-const ENTRIES_COUNT: usize = 1 << (PAGE_SHIFT - 3);
+const PAGE_PFN_MASK: usize = ((1 << 44) - 1) << PAGE_PFN_SHIFT;
+pub const ENTRIES_COUNT: usize = 1 << (PAGE_SHIFT - 3);
+
+/// Virtual address bits of the paging mode in use: 39, 48 or 57.
+static PAGING_BITS: AtomicUsize = AtomicUsize::new(ASPACE_BITS);
+
+/// Selects the paging mode (Sv39, Sv48 or Sv57) by its virtual address bits.
+///
+/// Must be called before any page table is built.
+pub fn set_aspace_bits(bits: usize) {
+    assert!(bits == 39 || bits == 48 || bits == 57);
+    PAGING_BITS.store(bits, Ordering::Relaxed);
+}
+
+/// Returns the virtual address bits of the paging mode in use.
+#[inline]
+pub fn aspace_bits() -> usize {
+    PAGING_BITS.load(Ordering::Relaxed)
+}
+
+/// Returns the number of page table levels of the paging mode in use.
+#[inline]
+pub fn levels() -> usize {
+    (aspace_bits() - PAGE_SHIFT) / (PAGE_SHIFT - 3)
+}
 
 #[derive(Clone, Copy)]
 #[repr(transparent)]
 pub struct PTEntry(u64);
 
 impl PTEntry {
-    pub fn set(&mut self, pa: usize, flags: usize) {
-        self.0 = Self::make(phys_pfn(pa), flags);
+    /// Makes the entry a leaf mapping `pa` with `flags`.
+    pub fn set(&mut self, pa: usize, flags: MappingFlags) {
+        self.set_raw(pa, flags.to_pte_bits());
+    }
+
+    fn set_raw(&mut self, pa: usize, bits: usize) {
+        self.0 = Self::make(phys_pfn(pa), bits);
     }
 
     fn make(pfn: usize, prot: usize) -> u64 {
@@ -48,15 +91,48 @@ impl PTEntry {
     fn is_present(&self) -> bool {
         (self.0 as usize & _PAGE_V) == _PAGE_V
     }
-    fn is_unused(&self) -> bool {
+    pub fn is_unused(&self) -> bool {
         self.0 == 0
     }
+    fn is_lazy(&self) -> bool {
+        !self.is_present() && (self.0 as usize & _PAGE_LAZY) != 0
+    }
+    fn is_leaf(&self) -> bool {
+        (self.0 as usize & (_PAGE_R | _PAGE_W | _PAGE_E)) != 0
+    }
     pub fn paddr(&self) -> usize {
-        pfn_phys(self.0 as usize >> PAGE_PFN_SHIFT)
+        pfn_phys((self.0 as usize & PAGE_PFN_MASK) >> PAGE_PFN_SHIFT)
+    }
+    pub fn flags(&self) -> MappingFlags {
+        MappingFlags::from_pte_bits(self.raw_flags())
+    }
+    fn raw_flags(&self) -> usize {
+        self.0 as usize & !PAGE_PFN_MASK
+    }
+    fn set_flags(&mut self, flags: MappingFlags) {
+        let bits = flags.to_pte_bits();
+        let bits = if self.is_lazy() {
+            (bits & !_PAGE_V) | _PAGE_LAZY
+        } else {
+            bits
+        };
+        self.0 = ((self.0 as usize & PAGE_PFN_MASK) | bits) as u64;
     }
-    pub fn flags(&self) -> usize {
-        self.0 as usize & ((1 << PAGE_PFN_SHIFT) - 1)
+}
+
+/// Flushes the TLB entries of `va` in every address space, global ones
+/// included, or the whole TLB if `None`.
+#[inline]
+fn flush_tlb(va: Option<usize>) {
+    // With `rs2 = x0`, not a register holding ASID 0, which would only
+    // flush the non-global entries tagged with ASID 0.
+    #[cfg(target_arch = "riscv64")]
+    match va {
+        Some(va) => unsafe { core::arch::asm!("sfence.vma {}, zero", in(reg) va) },
+        None => riscv::asm::sfence_vma_all(),
     }
+    #[cfg(not(target_arch = "riscv64"))]
+    let _ = va;
 }
 
 pub struct PageTable<'a> {
@@ -77,13 +153,14 @@ impl PageTable<'_> {
         Self { level, table }
     }
 
-    const fn entry_shift(&self) -> usize {
-        ASPACE_BITS - (self.level + 1) * (PAGE_SHIFT - 3)
+    fn entry_shift(&self) -> usize {
+        aspace_bits() - (self.level + 1) * (PAGE_SHIFT - 3)
     }
-    const fn entry_size(&self) -> usize {
+    /// Returns the size mapped by one entry of this table.
+    pub fn entry_size(&self) -> usize {
         1 << self.entry_shift()
     }
-    pub const fn entry_index(&self, va: usize) -> usize {
+    pub fn entry_index(&self, va: usize) -> usize {
         (va >> self.entry_shift()) & (ENTRIES_COUNT - 1)
     }
 
@@ -93,17 +170,24 @@ impl PageTable<'_> {
         mut pa: usize,
         mut total_size: usize,
         best_size: usize,
-        flags: usize,
+        flags: MappingFlags,
     ) -> PagingResult {
         assert!(is_aligned(va, best_size));
         assert!(is_aligned(pa, best_size));
         assert!(is_aligned(total_size, best_size));
+        if total_size == 0 {
+            return Ok(());
+        }
         let entry_size = self.entry_size();
         let next_size = min(entry_size, total_size);
         while total_size >= next_size {
             let index = self.entry_index(va);
             if entry_size == best_size {
+                if !self.table[index].is_unused() {
+                    return Err(PagingError::AlreadyMapped);
+                }
                 self.table[index].set(pa, flags);
+                flush_tlb(Some(va));
             } else {
                 let mut pt = self.next_table_mut(index)?;
                 pt.map(va, pa, next_size, best_size, flags)?;
@@ -120,7 +204,7 @@ impl PageTable<'_> {
         mut pa: usize,
         mut total_size: usize,
         best_size: usize,
-        flags: usize,
+        flags: MappingFlags,
     ) -> PagingResult {
         let mut map_size = best_size;
         if total_size < best_size {
@@ -147,25 +231,206 @@ impl PageTable<'_> {
             Ok(())
         }
     }
+    /// Reserves `[va, va + size)` to be backed by zeroed 4K frames on first
+    /// access, see [`PageTable::populate_lazy`]. Nothing is allocated except
+    /// intermediate tables.
+    pub fn map_lazy(&mut self, va: usize, size: usize, flags: MappingFlags) -> PagingResult {
+        assert!(is_aligned(va, PAGE_SIZE));
+        assert!(is_aligned(size, PAGE_SIZE));
+        let entry = (flags.to_pte_bits() & !_PAGE_V) | _PAGE_LAZY;
+        for va in (va..va + size).step_by(PAGE_SIZE) {
+            self.set_leaf_4k(va, entry)?;
+        }
+        Ok(())
+    }
+
+    fn set_leaf_4k(&mut self, va: usize, bits: usize) -> PagingResult {
+        let index = self.entry_index(va);
+        if self.entry_size() == PAGE_SIZE {
+            if !self.table[index].is_unused() {
+                return Err(PagingError::AlreadyMapped);
+            }
+            self.table[index] = PTEntry(bits as u64);
+            flush_tlb(Some(va));
+            Ok(())
+        } else {
+            self.next_table_mut(index)?.set_leaf_4k(va, bits)
+        }
+    }
+
+    /// Backs the lazy page containing `va` with a newly allocated zeroed
+    /// frame, if `access` is permitted by its flags.
+    ///
+    /// Returns the physical address of the frame, or `None` if the page is
+    /// not lazily mapped or the access is not permitted. The caller owns the
+    /// frame (allocated with the layout of a page).
+    pub fn populate_lazy(&mut self, va: usize, access: AccessType) -> Option<usize> {
+        let index = self.entry_index(va);
+        let entry = self.table[index];
+        if entry.is_present() && !entry.is_leaf() {
+            return self.next_table(index).ok()?.populate_lazy(va, access);
+        }
+        if !entry.is_lazy() {
+            return None;
+        }
+        let flags = entry.raw_flags();
+        let allowed = match access {
+            AccessType::Read => _PAGE_R,
+            AccessType::Write => _PAGE_W,
+            AccessType::Execute => _PAGE_E,
+        };
+        if flags & allowed == 0 {
+            return None;
+        }
+        let frame = Self::try_alloc_table(0).ok()?.root_paddr();
+        self.table[index].set_raw(frame, (flags & !_PAGE_LAZY) | _PAGE_V);
+        flush_tlb(Some(va));
+        Some(frame)
+    }
+
+    /// Unmaps `[va, va + size)`. Lazily mapped pages are dropped as well.
+    ///
+    /// The frames are not freed, they belong to whoever mapped them.
+    pub fn unmap(&mut self, va: usize, size: usize) -> PagingResult {
+        self.update_leaves(va, size, |entry| *entry = PTEntry(0))
+    }
+
+    /// Changes the flags of the mapped range `[va, va + size)`.
+    pub fn protect(&mut self, va: usize, size: usize, flags: MappingFlags) -> PagingResult {
+        self.update_leaves(va, size, |entry| entry.set_flags(flags))
+    }
+
+    /// Translates `va`, returning the physical address, the flags and the
+    /// size of the page that maps it.
+    pub fn query(&self, va: usize) -> PagingResult<(usize, MappingFlags, usize)> {
+        let index = self.entry_index(va);
+        let entry = self.table[index];
+        if !entry.is_present() {
+            return Err(PagingError::NotMapped);
+        }
+        let size = self.entry_size();
+        if entry.is_leaf() {
+            return Ok((entry.paddr() + align_offset(va, size), entry.flags(), size));
+        }
+        self.next_table(index)?.query(va)
+    }
+
+    fn update_leaves<F>(&mut self, va: usize, size: usize, mut f: F) -> PagingResult
+    where
+        F: FnMut(&mut PTEntry),
+    {
+        assert!(is_aligned(va, PAGE_SIZE));
+        assert!(is_aligned(size, PAGE_SIZE));
+        let end = va + size;
+        let mut va = va;
+        while va < end {
+            va += self.update_leaf(va, end, &mut f)?;
+        }
+        Ok(())
+    }
+
+    /// Applies `f` to the leaf entry mapping `va`, which must fit in
+    /// `[va, end)`, and returns the size of its page.
+    fn update_leaf<F>(&mut self, va: usize, end: usize, f: &mut F) -> PagingResult<usize>
+    where
+        F: FnMut(&mut PTEntry),
+    {
+        let index = self.entry_index(va);
+        let entry = self.table[index];
+        if entry.is_present() && !entry.is_leaf() {
+            return self.next_table(index)?.update_leaf(va, end, f);
+        }
+        if !entry.is_present() && !entry.is_lazy() {
+            return Err(PagingError::NotMapped);
+        }
+        let size = self.entry_size();
+        if !is_aligned(va, size) || end - va < size {
+            return Err(PagingError::MappedToHugePage);
+        }
+        f(&mut self.table[index]);
+        flush_tlb(Some(va));
+        Ok(size)
+    }
+
     fn next_table_mut(&mut self, index: usize) -> PagingResult<PageTable> {
-        if self.table[index].is_unused() {
-            let table = Self::alloc_table(self.level + 1);
-            self.table[index].set(table.root_paddr(), PAGE_TABLE);
+        let entry = self.table[index];
+        if entry.is_unused() {
+            let table = Self::try_alloc_table(self.level + 1)?;
+            self.table[index].set_raw(table.root_paddr(), PAGE_TABLE);
             Ok(table)
+        } else if entry.is_present() && entry.is_leaf() {
+            Err(PagingError::MappedToHugePage)
         } else {
             self.next_table(index)
         }
     }
     pub fn next_table(&self, index: usize) -> PagingResult<PageTable> {
-        assert!(self.table[index].is_present());
-        let pa = self.table[index].paddr();
-        let va = phys_to_virt(pa);
+        let entry = self.table[index];
+        if !entry.is_present() {
+            return Err(PagingError::NotMapped);
+        }
+        if entry.is_leaf() {
+            return Err(PagingError::MappedToHugePage);
+        }
+        let va = phys_to_virt(entry.paddr());
         Ok(Self::init(va, self.level + 1))
     }
+    /// Frees the intermediate tables under `[va, end)` that no longer map
+    /// anything, skipping the entries of this table with indices in `skip`.
+    /// Returns whether this table is left empty.
+    fn reclaim(&mut self, va: usize, end: usize, skip: &Range<usize>) -> bool {
+        let size = self.entry_size();
+        let mut va = va;
+        while va < end {
+            let index = self.entry_index(va);
+            let entry = self.table[index];
+            let next = min(align_down(va, size).wrapping_add(size), end);
+            if !skip.contains(&index) && entry.is_present() && !entry.is_leaf() {
+                let mut table = Self::init(phys_to_virt(entry.paddr()), self.level + 1);
+                if table.reclaim(va, next, &(0..0)) {
+                    self.table[index] = PTEntry(0);
+                    flush_tlb(None);
+                    Self::free_table(table.table.as_ptr() as usize);
+                }
+            }
+            if next <= va {
+                break; // wrapped around the top of the address space
+            }
+            va = next;
+        }
+        self.table.iter().all(|e| e.is_unused())
+    }
+
+    /// Frees all intermediate tables below this one, skipping the entries
+    /// with indices in `skip`. Leaf frames are not freed.
+    fn free_subtables(&mut self, skip: &Range<usize>) {
+        for index in 0..ENTRIES_COUNT {
+            let entry = self.table[index];
+            if !skip.contains(&index) && entry.is_present() && !entry.is_leaf() {
+                let mut table = Self::init(phys_to_virt(entry.paddr()), self.level + 1);
+                table.free_subtables(&(0..0));
+                Self::free_table(table.table.as_ptr() as usize);
+                self.table[index] = PTEntry(0);
+            }
+        }
+    }
+
+    /// Frees a table allocated by [`PageTable::alloc_table`] at `vaddr`.
+    fn free_table(vaddr: usize) {
+        let layout = Layout::from_size_align(PAGE_SIZE, PAGE_SIZE).unwrap();
+        unsafe { alloc::alloc::dealloc(vaddr as *mut u8, layout) }
+    }
+
     pub fn alloc_table(level: usize) -> Self {
+        Self::try_alloc_table(level).expect("failed to allocate page table")
+    }
+    fn try_alloc_table(level: usize) -> PagingResult<Self> {
         let layout = Layout::from_size_align(PAGE_SIZE, PAGE_SIZE).unwrap();
         let ptr = unsafe { alloc::alloc::alloc_zeroed(layout) };
-        Self::init(ptr as usize, level)
+        if ptr.is_null() {
+            return Err(PagingError::NoMemory);
+        }
+        Ok(Self::init(ptr as usize, level))
     }
     pub fn root_paddr(&self) -> usize {
         virt_to_phys(self.table.as_ptr() as usize)
@@ -173,4 +438,11 @@ impl PageTable<'_> {
     pub fn entry_at(&self, index: usize) -> PTEntry {
         self.table[index]
     }
+    pub fn set_entry_at(&mut self, index: usize, entry: PTEntry) {
+        self.table[index] = entry;
+    }
+    /// Points the entry at `index` to the next-level table at `table_pa`.
+    pub fn link_table(&mut self, index: usize, table_pa: usize) {
+        self.table[index].set_raw(table_pa, PAGE_TABLE);
+    }
 }
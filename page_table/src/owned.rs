@@ -0,0 +1,68 @@
+use crate::{ENTRIES_COUNT, PageTable, PagingResult};
+use core::ops::{Deref, DerefMut, Range};
+
+/// A page table that owns its root and the intermediate tables allocated
+/// through it, and frees them when they become empty or on drop.
+///
+/// Root entries copied from another table with [`OwnedPageTable::share`]
+/// are never freed. Leaf frames always belong to whoever mapped them.
+pub struct OwnedPageTable {
+    pt: PageTable<'static>,
+    /// Indices of the root entries borrowed from another table.
+    shared: Range<usize>,
+}
+
+impl OwnedPageTable {
+    /// Allocates an empty page table.
+    pub fn new() -> Self {
+        Self {
+            pt: PageTable::alloc_table(0),
+            shared: 0..0,
+        }
+    }
+
+    /// Copies the root entries `indices` of `other` into this table. They
+    /// stay owned by `other`.
+    pub fn share(&mut self, other: &PageTable, indices: Range<usize>) {
+        assert!(self.shared.is_empty(), "root entries are already shared");
+        assert!(indices.end <= ENTRIES_COUNT);
+        for i in indices.clone() {
+            self.pt.set_entry_at(i, other.entry_at(i));
+        }
+        self.shared = indices;
+    }
+
+    /// Unmaps `[va, va + size)` and frees the intermediate tables that no
+    /// longer map anything.
+    pub fn unmap(&mut self, va: usize, size: usize) -> PagingResult {
+        self.pt.unmap(va, size)?;
+        self.pt.reclaim(va, va + size, &self.shared);
+        Ok(())
+    }
+}
+
+impl Default for OwnedPageTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for OwnedPageTable {
+    type Target = PageTable<'static>;
+    fn deref(&self) -> &Self::Target {
+        &self.pt
+    }
+}
+
+impl DerefMut for OwnedPageTable {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.pt
+    }
+}
+
+impl Drop for OwnedPageTable {
+    fn drop(&mut self) {
+        self.pt.free_subtables(&self.shared);
+        PageTable::free_table(self.pt.table.as_ptr() as usize);
+    }
+}
@@ -1,5 +1,5 @@
 use axconfig::SIZE_2M;
-use page_table::{PAGE_KERNEL_RWX, PageTable};
+use page_table::{MappingFlags, PageTable};
 use std::println;
 
 #[test]
@@ -12,7 +12,7 @@ fn test_final() {
         0x8020_a000,
         0x7df6000,
         SIZE_2M,
-        PAGE_KERNEL_RWX,
+        MappingFlags::KERNEL_RWX,
     );
     let pgd_index = pgd.entry_index(0xffff_ffc0_8020_a000);
     assert_eq!(pgd_index, 258);
@@ -23,5 +23,5 @@ fn test_final() {
     let pt_index = pt.entry_index(0xffff_ffc0_8020_a000);
     assert_eq!(pt_index, 10);
     assert_eq!(pt.entry_at(pt_index).paddr(), 0x8020_a000);
-    assert_eq!(pt.entry_at(pt_index).flags(), PAGE_KERNEL_RWX);
+    assert_eq!(pt.entry_at(pt_index).flags(), MappingFlags::KERNEL_RWX);
 }
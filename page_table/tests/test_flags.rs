@@ -0,0 +1,24 @@
+use page_table::{MappingFlags, PageTable, enable_svpbmt};
+
+#[test]
+fn test_flags() {
+    let pgd_mem: [u64; 512] = [0; 512];
+    let mut pgd: PageTable = PageTable::init(pgd_mem.as_ptr() as usize, 0);
+
+    let user_rx = MappingFlags::READ | MappingFlags::EXECUTE | MappingFlags::USER;
+    assert_eq!(MappingFlags::from_pte_bits(user_rx.to_pte_bits()), user_rx);
+
+    // Without Svpbmt the memory type bits are reserved and left clear.
+    let device = MappingFlags::READ | MappingFlags::WRITE | MappingFlags::DEVICE;
+    assert_eq!(device.to_pte_bits() >> 61, 0);
+
+    enable_svpbmt();
+    assert_eq!(device.to_pte_bits() >> 61, 2);
+    let va = 0xffff_ffc0_1000_0000;
+    pgd.map(va, 0x1000_0000, 0x1000, 0x1000, device).unwrap();
+    assert_eq!(pgd.query(va + 8), Ok((0x1000_0008, device, 0x1000)));
+
+    let uncached = MappingFlags::READ | MappingFlags::UNCACHED;
+    pgd.protect(va, 0x1000, uncached).unwrap();
+    assert_eq!(pgd.query(va), Ok((0x1000_0000, uncached, 0x1000)));
+}
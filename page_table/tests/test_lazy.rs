@@ -0,0 +1,48 @@
+use axconfig::PAGE_SIZE;
+use page_table::{AccessType, MappingFlags, PageTable};
+
+const USER_RO: MappingFlags = MappingFlags::READ.union(MappingFlags::USER);
+const USER_RW: MappingFlags = MappingFlags::READ
+    .union(MappingFlags::WRITE)
+    .union(MappingFlags::USER);
+
+#[test]
+fn test_lazy() {
+    let pgd_mem: [u64; 512] = [0; 512];
+    let mut pgd: PageTable = PageTable::init(pgd_mem.as_ptr() as usize, 0);
+
+    let va = 0x10_0000;
+    pgd.map_lazy(va, 4 * PAGE_SIZE, USER_RW).unwrap();
+    pgd.map_lazy(va + 4 * PAGE_SIZE, PAGE_SIZE, USER_RO)
+        .unwrap();
+
+    // Executing or touching outside the reservation is not resolved.
+    assert!(pgd.populate_lazy(va, AccessType::Execute).is_none());
+    assert!(
+        pgd.populate_lazy(va + 5 * PAGE_SIZE, AccessType::Read)
+            .is_none()
+    );
+    assert!(
+        pgd.populate_lazy(va + 4 * PAGE_SIZE, AccessType::Write)
+            .is_none()
+    );
+
+    let frame = pgd
+        .populate_lazy(va + PAGE_SIZE + 8, AccessType::Write)
+        .unwrap();
+    let pmd = pgd.next_table(pgd.entry_index(va)).unwrap();
+    let pt = pmd.next_table(pmd.entry_index(va)).unwrap();
+    let entry = pt.entry_at(pt.entry_index(va + PAGE_SIZE));
+    assert_eq!(entry.paddr(), frame);
+    assert_eq!(entry.flags(), USER_RW);
+
+    // Already populated: nothing more to do.
+    assert!(
+        pgd.populate_lazy(va + PAGE_SIZE, AccessType::Read)
+            .is_none()
+    );
+    assert!(
+        pgd.populate_lazy(va + 4 * PAGE_SIZE, AccessType::Read)
+            .is_some()
+    );
+}
@@ -0,0 +1,56 @@
+use axconfig::PAGE_SIZE;
+use page_table::{MappingFlags, OwnedPageTable, PageTable};
+
+const USER_RW: MappingFlags = MappingFlags::READ
+    .union(MappingFlags::WRITE)
+    .union(MappingFlags::USER);
+
+#[test]
+fn test_reclaim_on_unmap() {
+    let mut pt = OwnedPageTable::new();
+    let va = 0x40_0000;
+    pt.map(va, 0x8020_0000, 2 * PAGE_SIZE, PAGE_SIZE, USER_RW)
+        .unwrap();
+    pt.map_lazy(va + 0x4000_0000, PAGE_SIZE, USER_RW).unwrap();
+    let index = pt.entry_index(va);
+    let lazy_index = pt.entry_index(va + 0x4000_0000);
+    assert_ne!(index, lazy_index);
+
+    // The last-level table still maps one page.
+    pt.unmap(va, PAGE_SIZE).unwrap();
+    assert!(!pt.entry_at(index).is_unused());
+    assert!(pt.query(va + PAGE_SIZE).is_ok());
+
+    pt.unmap(va + PAGE_SIZE, PAGE_SIZE).unwrap();
+    assert!(pt.entry_at(index).is_unused());
+
+    pt.unmap(va + 0x4000_0000, PAGE_SIZE).unwrap();
+    assert!(pt.entry_at(lazy_index).is_unused());
+}
+
+#[test]
+fn test_shared_entries() {
+    let kernel_mem: [u64; 512] = [0; 512];
+    let mut kernel: PageTable = PageTable::init(kernel_mem.as_ptr() as usize, 0);
+    let kva = 0xffff_ffc0_8020_0000;
+    kernel
+        .map(
+            kva,
+            0x8020_0000,
+            PAGE_SIZE,
+            PAGE_SIZE,
+            MappingFlags::KERNEL_RW,
+        )
+        .unwrap();
+    let kindex = kernel.entry_index(kva);
+
+    {
+        let mut pt = OwnedPageTable::new();
+        pt.share(&kernel, 256..512);
+        assert_eq!(pt.entry_at(kindex).paddr(), kernel.entry_at(kindex).paddr());
+        pt.map(0x1000, 0x8030_0000, PAGE_SIZE, PAGE_SIZE, USER_RW)
+            .unwrap();
+        // Dropping frees only the user half.
+    }
+    assert_eq!(kernel.query(kva).unwrap().0, 0x8020_0000);
+}
@@ -0,0 +1,33 @@
+use axconfig::{PAGE_SIZE, SIZE_1G};
+use page_table::{MappingFlags, PageTable, levels, set_aspace_bits};
+
+#[test]
+fn test_sv48() {
+    set_aspace_bits(48);
+    assert_eq!(levels(), 4);
+
+    let pgd_mem: [u64; 512] = [0; 512];
+    let mut pgd: PageTable = PageTable::init(pgd_mem.as_ptr() as usize, 0);
+    assert_eq!(pgd.entry_size(), 512 * SIZE_1G);
+
+    // Beyond the 256G reachable with Sv39.
+    let va = 0x7f_0000_0000;
+    pgd.map(
+        va,
+        0x8020_0000,
+        PAGE_SIZE,
+        PAGE_SIZE,
+        MappingFlags::KERNEL_RW,
+    )
+    .unwrap();
+    assert_eq!(pgd.entry_index(va), 0);
+    let pud = pgd.next_table(0).unwrap();
+    assert_eq!(pud.entry_index(va), 0x1fc);
+    let pmd = pud.next_table(0x1fc).unwrap();
+    let pt = pmd.next_table(pmd.entry_index(va)).unwrap();
+    assert_eq!(pt.entry_size(), PAGE_SIZE);
+    assert_eq!(
+        pgd.query(va + 8),
+        Ok((0x8020_0008, MappingFlags::KERNEL_RW, PAGE_SIZE))
+    );
+}
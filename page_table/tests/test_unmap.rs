@@ -0,0 +1,71 @@
+use axconfig::{PAGE_SIZE, SIZE_2M};
+use page_table::{MappingFlags, PageTable, PagingError};
+
+const KERNEL_RO: MappingFlags = MappingFlags::READ;
+
+#[test]
+fn test_unmap_protect_query() {
+    let pgd_mem: [u64; 512] = [0; 512];
+    let mut pgd: PageTable = PageTable::init(pgd_mem.as_ptr() as usize, 0);
+
+    let va = 0xffff_ffc0_8020_0000;
+    pgd.map(
+        va,
+        0x8020_0000,
+        4 * PAGE_SIZE,
+        PAGE_SIZE,
+        MappingFlags::KERNEL_RW,
+    )
+    .unwrap();
+    assert_eq!(
+        pgd.map(
+            va,
+            0x8020_0000,
+            PAGE_SIZE,
+            PAGE_SIZE,
+            MappingFlags::KERNEL_RW
+        ),
+        Err(PagingError::AlreadyMapped)
+    );
+    assert_eq!(
+        pgd.query(va + PAGE_SIZE + 0x123),
+        Ok((0x8020_1123, MappingFlags::KERNEL_RW, PAGE_SIZE))
+    );
+
+    pgd.protect(va + PAGE_SIZE, PAGE_SIZE, KERNEL_RO).unwrap();
+    assert_eq!(pgd.query(va + PAGE_SIZE).unwrap().1, KERNEL_RO);
+    assert_eq!(pgd.query(va).unwrap().1, MappingFlags::KERNEL_RW);
+
+    pgd.unmap(va, 2 * PAGE_SIZE).unwrap();
+    assert_eq!(pgd.query(va), Err(PagingError::NotMapped));
+    assert_eq!(pgd.query(va + PAGE_SIZE), Err(PagingError::NotMapped));
+    assert!(pgd.query(va + 2 * PAGE_SIZE).is_ok());
+    assert_eq!(pgd.unmap(va, PAGE_SIZE), Err(PagingError::NotMapped));
+}
+
+#[test]
+fn test_huge_page() {
+    let pgd_mem: [u64; 512] = [0; 512];
+    let mut pgd: PageTable = PageTable::init(pgd_mem.as_ptr() as usize, 0);
+
+    let va = 0xffff_ffc0_8040_0000;
+    pgd.map(va, 0x8040_0000, SIZE_2M, SIZE_2M, MappingFlags::KERNEL_RW)
+        .unwrap();
+    assert_eq!(
+        pgd.query(va + 0x1000),
+        Ok((0x8040_1000, MappingFlags::KERNEL_RW, SIZE_2M))
+    );
+    assert_eq!(pgd.unmap(va, PAGE_SIZE), Err(PagingError::MappedToHugePage));
+    assert_eq!(
+        pgd.map(
+            va,
+            0x8040_0000,
+            PAGE_SIZE,
+            PAGE_SIZE,
+            MappingFlags::KERNEL_RW
+        ),
+        Err(PagingError::MappedToHugePage)
+    );
+    pgd.unmap(va, SIZE_2M).unwrap();
+    assert_eq!(pgd.query(va), Err(PagingError::NotMapped));
+}
@@ -0,0 +1,315 @@
+#![no_std]
+//! Per-CPU data areas.
+//!
+//! Per-CPU statics are declared with [`def_percpu!`] and placed in the
+//! `.percpu` section, which only serves as a template. [`init`] copies the
+//! template into one area per CPU, and each CPU keeps the base address of its
+//! own area in `gp` (see [`set_local_thread_pointer`]).
+//!
+//! On other architectures, for host tests, the template is the `percpu`
+//! section and the base address is a plain static shared by all threads.
+
+use axconfig::SMP;
+use core::marker::PhantomData;
+
+/// Size reserved for the per-CPU data area of each CPU.
+pub const PERCPU_AREA_SIZE: usize = 0x1000;
+
+#[repr(C, align(64))]
+struct PerCpuArea([u8; PERCPU_AREA_SIZE]);
+
+static mut PERCPU_AREAS: [PerCpuArea; SMP] = [const { PerCpuArea([0; PERCPU_AREA_SIZE]) }; SMP];
+
+/// Returns the size of the `.percpu` template.
+pub fn percpu_area_size() -> usize {
+    arch::template_end() - arch::template_start()
+}
+
+/// Returns the base address of the per-CPU data area of the given CPU.
+pub fn percpu_area_base(cpu_id: usize) -> usize {
+    assert!(cpu_id < SMP);
+    unsafe { (&raw const PERCPU_AREAS[cpu_id]) as usize }
+}
+
+/// Initializes the per-CPU data areas of all CPUs by copying the template.
+///
+/// It must be called once on the primary CPU, before any per-CPU variable
+/// is written.
+pub fn init() {
+    let size = percpu_area_size();
+    assert!(
+        size <= PERCPU_AREA_SIZE,
+        "per-CPU data ({:#x}) exceeds PERCPU_AREA_SIZE",
+        size
+    );
+    for cpu_id in 0..SMP {
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                arch::template_start() as *const u8,
+                percpu_area_base(cpu_id) as *mut u8,
+                size,
+            );
+        }
+    }
+}
+
+/// Reads the base address of the per-CPU data area of the current CPU.
+#[inline]
+pub fn get_local_thread_pointer() -> usize {
+    arch::read_base()
+}
+
+/// Points `gp` at the per-CPU data area of the given CPU.
+pub fn set_local_thread_pointer(cpu_id: usize) {
+    arch::write_base(percpu_area_base(cpu_id));
+}
+
+/// A per-CPU variable, declared with [`def_percpu!`].
+pub struct PerCpu<T> {
+    template: *mut T,
+    _marker: PhantomData<T>,
+}
+
+unsafe impl<T> Sync for PerCpu<T> {}
+
+impl<T> PerCpu<T> {
+    #[doc(hidden)]
+    pub const fn new(template: *mut T) -> Self {
+        Self {
+            template,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the offset of this variable within a per-CPU data area.
+    #[inline]
+    pub fn offset(&self) -> usize {
+        self.template as usize - arch::template_start()
+    }
+
+    /// Returns the raw pointer of this variable on the current CPU.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure the current task is not migrated to another
+    /// CPU while the pointer is in use, e.g. by disabling IRQs or preemption.
+    #[inline]
+    pub unsafe fn current_ptr(&self) -> *mut T {
+        (get_local_thread_pointer() + self.offset()) as *mut T
+    }
+
+    /// Returns a reference to this variable on the current CPU.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`PerCpu::current_ptr`].
+    #[inline]
+    pub unsafe fn current_ref_raw(&self) -> &T {
+        unsafe { &*self.current_ptr() }
+    }
+
+    /// Returns a mutable reference to this variable on the current CPU.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`PerCpu::current_ptr`], and the caller must also ensure
+    /// there is no other reference to the variable.
+    #[inline]
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn current_ref_mut_raw(&self) -> &mut T {
+        unsafe { &mut *self.current_ptr() }
+    }
+
+    /// Returns the raw pointer of this variable on the given CPU.
+    ///
+    /// # Safety
+    ///
+    /// The caller must synchronize with the accesses of the remote CPU.
+    #[inline]
+    pub unsafe fn remote_ptr(&self, cpu_id: usize) -> *mut T {
+        (percpu_area_base(cpu_id) + self.offset()) as *mut T
+    }
+
+    /// Manipulates this variable on the current CPU with local IRQs disabled.
+    pub fn with_current<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        let _guard = arch::IrqGuard::new();
+        f(unsafe { self.current_ref_mut_raw() })
+    }
+}
+
+impl<T: Copy> PerCpu<T> {
+    /// Reads this variable on the current CPU.
+    #[inline]
+    pub fn read_current(&self) -> T {
+        self.with_current(|v| *v)
+    }
+
+    /// Writes this variable on the current CPU.
+    #[inline]
+    pub fn write_current(&self, val: T) {
+        self.with_current(|v| *v = val)
+    }
+}
+
+/// Defines per-CPU statics.
+///
+/// The initial value is copied bitwise into the area of every CPU by
+/// [`init`], so it should not own any resource.
+///
+/// ```ignore
+/// percpu::def_percpu! {
+///     static CPU_ID: usize = 0;
+/// }
+///
+/// CPU_ID.write_current(1);
+/// assert_eq!(CPU_ID.read_current(), 1);
+/// ```
+#[macro_export]
+macro_rules! def_percpu {
+    ($($(#[$attr:meta])* $vis:vis static $name:ident: $ty:ty = $init:expr;)+) => {
+        $(
+            $(#[$attr])*
+            $vis static $name: $crate::PerCpu<$ty> = {
+                #[cfg_attr(target_arch = "riscv64", unsafe(link_section = ".percpu"))]
+                #[cfg_attr(not(target_arch = "riscv64"), unsafe(link_section = "percpu"))]
+                static mut TEMPLATE: $ty = $init;
+                $crate::PerCpu::new(&raw mut TEMPLATE)
+            };
+        )+
+    };
+}
+
+#[cfg(target_arch = "riscv64")]
+mod arch {
+    pub use kernel_guard::IrqSave as IrqGuard;
+
+    unsafe extern "C" {
+        fn _percpu_start();
+        fn _percpu_end();
+    }
+
+    #[inline]
+    pub fn template_start() -> usize {
+        _percpu_start as usize
+    }
+
+    #[inline]
+    pub fn template_end() -> usize {
+        _percpu_end as usize
+    }
+
+    #[inline]
+    pub fn read_base() -> usize {
+        let base: usize;
+        unsafe { core::arch::asm!("mv {}, gp", out(reg) base) };
+        base
+    }
+
+    #[inline]
+    pub fn write_base(base: usize) {
+        unsafe { core::arch::asm!("mv gp, {}", in(reg) base) };
+    }
+}
+
+#[cfg(not(target_arch = "riscv64"))]
+mod arch {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    // There are no IRQs to disable on the host.
+    pub use kernel_guard::NoOp as IrqGuard;
+
+    // The linker defines these for sections named like identifiers. The
+    // template is never empty, as this crate defines a per-CPU static.
+    unsafe extern "C" {
+        static __start_percpu: u8;
+        static __stop_percpu: u8;
+    }
+
+    crate::def_percpu! {
+        static _ANCHOR: u8 = 0;
+    }
+
+    static BASE: AtomicUsize = AtomicUsize::new(0);
+
+    pub fn template_start() -> usize {
+        // Keep the anchor, hence the section.
+        core::hint::black_box(&_ANCHOR);
+        &raw const __start_percpu as usize
+    }
+
+    pub fn template_end() -> usize {
+        &raw const __stop_percpu as usize
+    }
+
+    pub fn read_base() -> usize {
+        BASE.load(Ordering::Relaxed)
+    }
+
+    pub fn write_base(base: usize) {
+        BASE.store(base, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::sync::Mutex;
+
+    def_percpu! {
+        static COUNTER: usize = 7;
+        static PAIR: (u32, u64) = (1, 2);
+    }
+
+    // The base is shared by all test threads.
+    static LOCK: Mutex<()> = Mutex::new(());
+
+    #[test]
+    fn offsets_within_template() {
+        let size = percpu_area_size();
+        assert!(size > 0 && size <= PERCPU_AREA_SIZE);
+        for offset in [COUNTER.offset(), PAIR.offset()] {
+            assert!(offset < size);
+        }
+        assert_ne!(COUNTER.offset(), PAIR.offset());
+        assert_eq!(
+            percpu_area_base(SMP - 1) - percpu_area_base(0),
+            (SMP - 1) * PERCPU_AREA_SIZE
+        );
+    }
+
+    #[test]
+    fn init_copies_template() {
+        let _lock = LOCK.lock().unwrap();
+        init();
+        for cpu_id in 0..SMP {
+            set_local_thread_pointer(cpu_id);
+            assert_eq!(get_local_thread_pointer(), percpu_area_base(cpu_id));
+            assert_eq!(COUNTER.read_current(), 7);
+            assert_eq!(PAIR.read_current(), (1, 2));
+        }
+    }
+
+    #[test]
+    fn write_current_is_per_cpu() {
+        let _lock = LOCK.lock().unwrap();
+        init();
+        for cpu_id in 0..SMP {
+            set_local_thread_pointer(cpu_id);
+            COUNTER.write_current(100 + cpu_id);
+        }
+        for cpu_id in 0..SMP {
+            set_local_thread_pointer(cpu_id);
+            assert_eq!(COUNTER.read_current(), 100 + cpu_id);
+            assert_eq!(unsafe { *COUNTER.remote_ptr(cpu_id) }, 100 + cpu_id);
+        }
+        // The template itself is left untouched.
+        init();
+        set_local_thread_pointer(0);
+        assert_eq!(COUNTER.read_current(), 7);
+    }
+}
@@ -0,0 +1,149 @@
+use crate::Scheduler;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use core::ops::Deref;
+use core::sync::atomic::{AtomicIsize, AtomicU64, Ordering};
+
+/// Weights of the nice values `-20..=19`, as in Linux: one nice level
+/// apart is about 1.25 times the CPU share.
+const NICE_TO_WEIGHT: [u64; 40] = [
+    88761, 71755, 56483, 46273, 36291, 29154, 23254, 18705, 14949, 11916, // -20..-11
+    9548, 7620, 6100, 4904, 3906, 3121, 2501, 1991, 1586, 1277, // -10..-1
+    1024, 820, 655, 526, 423, 335, 272, 215, 172, 137, // 0..9
+    110, 87, 70, 56, 45, 36, 29, 23, 18, 15, // 10..19
+];
+const NICE_0_WEIGHT: u64 = 1024;
+/// Virtual runtime a nice-0 task is charged per tick.
+const VRUNTIME_PER_TICK: u64 = 1024;
+
+/// A task of the [`CFScheduler`].
+pub struct CFSTask<T> {
+    inner: T,
+    vruntime: AtomicU64,
+    nice: AtomicIsize,
+    /// Breaks ties between equal virtual runtimes in the ready queue.
+    seq: AtomicU64,
+}
+
+impl<T> CFSTask<T> {
+    pub const fn new(inner: T) -> Self {
+        Self {
+            inner,
+            vruntime: AtomicU64::new(0),
+            nice: AtomicIsize::new(0),
+            seq: AtomicU64::new(0),
+        }
+    }
+
+    pub const fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Returns the virtual runtime, the ticks run weighted by the nice
+    /// value.
+    pub fn vruntime(&self) -> u64 {
+        self.vruntime.load(Ordering::Acquire)
+    }
+
+    /// Returns the nice value.
+    pub fn nice(&self) -> isize {
+        self.nice.load(Ordering::Acquire)
+    }
+
+    fn key(&self) -> (u64, u64) {
+        (self.vruntime(), self.seq.load(Ordering::Acquire))
+    }
+
+    fn tick(&self) -> u64 {
+        let weight = NICE_TO_WEIGHT[(self.nice() + 20) as usize];
+        let delta = VRUNTIME_PER_TICK * NICE_0_WEIGHT / weight;
+        self.vruntime.fetch_add(delta, Ordering::AcqRel) + delta
+    }
+}
+
+impl<T> Deref for CFSTask<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+/// Completely-fair-style scheduling: the ready task that has run the least
+/// virtual time runs next, and virtual time passes slower for tasks with
+/// lower nice values.
+pub struct CFScheduler<T> {
+    ready_queue: BTreeMap<(u64, u64), Arc<CFSTask<T>>>,
+    /// Lower bound of the virtual runtimes in the queue. Tasks that were
+    /// blocked start from it instead of catching up for the time they slept.
+    min_vruntime: u64,
+    next_seq: u64,
+}
+
+impl<T> CFScheduler<T> {
+    pub const fn new() -> Self {
+        Self {
+            ready_queue: BTreeMap::new(),
+            min_vruntime: 0,
+            next_seq: 0,
+        }
+    }
+
+    fn insert(&mut self, task: Arc<CFSTask<T>>) {
+        task.seq.store(self.next_seq, Ordering::Release);
+        self.next_seq += 1;
+        self.ready_queue.insert(task.key(), task);
+    }
+}
+
+impl<T> Default for CFScheduler<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Scheduler for CFScheduler<T> {
+    type SchedItem = Arc<CFSTask<T>>;
+
+    fn add_task(&mut self, task: Self::SchedItem) {
+        task.vruntime.fetch_max(self.min_vruntime, Ordering::AcqRel);
+        self.insert(task);
+    }
+
+    fn remove_task(&mut self, task: &Self::SchedItem) -> Option<Self::SchedItem> {
+        let key = task.key();
+        match self.ready_queue.get(&key) {
+            Some(t) if Arc::ptr_eq(t, task) => self.ready_queue.remove(&key),
+            _ => None,
+        }
+    }
+
+    fn pick_next_task(&mut self) -> Option<Self::SchedItem> {
+        let ((vruntime, _), task) = self.ready_queue.pop_first()?;
+        self.min_vruntime = self.min_vruntime.max(vruntime);
+        Some(task)
+    }
+
+    fn put_prev_task(&mut self, prev: Self::SchedItem, _preempt: bool) {
+        self.insert(prev);
+    }
+
+    fn task_tick(&mut self, current: &Self::SchedItem) -> bool {
+        let vruntime = current.tick();
+        self.ready_queue
+            .first_key_value()
+            .is_some_and(|(key, _)| key.0 < vruntime)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.ready_queue.is_empty()
+    }
+
+    fn set_priority(&mut self, task: &Self::SchedItem, prio: isize) -> bool {
+        if (-20..=19).contains(&prio) {
+            task.nice.store(prio, Ordering::Release);
+            true
+        } else {
+            false
+        }
+    }
+}
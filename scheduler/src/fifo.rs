@@ -0,0 +1,79 @@
+use crate::Scheduler;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use core::ops::Deref;
+
+/// A task of the [`FifoScheduler`].
+pub struct FifoTask<T> {
+    inner: T,
+}
+
+impl<T> FifoTask<T> {
+    pub const fn new(inner: T) -> Self {
+        Self { inner }
+    }
+
+    pub const fn inner(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T> Deref for FifoTask<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+/// Cooperative first-in-first-out scheduling: a task runs until it blocks
+/// or yields, and ticks never preempt it.
+pub struct FifoScheduler<T> {
+    ready_queue: VecDeque<Arc<FifoTask<T>>>,
+}
+
+impl<T> FifoScheduler<T> {
+    pub const fn new() -> Self {
+        Self {
+            ready_queue: VecDeque::new(),
+        }
+    }
+}
+
+impl<T> Default for FifoScheduler<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Scheduler for FifoScheduler<T> {
+    type SchedItem = Arc<FifoTask<T>>;
+
+    fn add_task(&mut self, task: Self::SchedItem) {
+        self.ready_queue.push_back(task);
+    }
+
+    fn remove_task(&mut self, task: &Self::SchedItem) -> Option<Self::SchedItem> {
+        let idx = self.ready_queue.iter().position(|t| Arc::ptr_eq(t, task))?;
+        self.ready_queue.remove(idx)
+    }
+
+    fn pick_next_task(&mut self) -> Option<Self::SchedItem> {
+        self.ready_queue.pop_front()
+    }
+
+    fn put_prev_task(&mut self, prev: Self::SchedItem, _preempt: bool) {
+        self.ready_queue.push_back(prev);
+    }
+
+    fn task_tick(&mut self, _current: &Self::SchedItem) -> bool {
+        false
+    }
+
+    fn is_empty(&self) -> bool {
+        self.ready_queue.is_empty()
+    }
+
+    fn set_priority(&mut self, _task: &Self::SchedItem, _prio: isize) -> bool {
+        false
+    }
+}
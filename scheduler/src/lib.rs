@@ -0,0 +1,50 @@
+//! Scheduling policies over the ready tasks of a run queue.
+//!
+//! Each normal policy wraps the tasks it schedules in its own task type,
+//! which keeps the per-task state of the policy and derefs to the inner
+//! task. The real-time policy schedules tasks above any normal policy.
+
+#![no_std]
+
+extern crate alloc;
+
+mod cfs;
+mod fifo;
+mod round_robin;
+mod rt;
+
+pub use cfs::{CFSTask, CFScheduler};
+pub use fifo::{FifoScheduler, FifoTask};
+pub use round_robin::{RRScheduler, RRTask};
+pub use rt::{RtPriority, RtScheduler};
+
+/// A scheduling policy. It holds the ready tasks only: the running task is
+/// taken out by `pick_next_task` and given back by `put_prev_task`.
+pub trait Scheduler {
+    /// The task handle being scheduled.
+    type SchedItem;
+
+    /// Adds a task that became ready.
+    fn add_task(&mut self, task: Self::SchedItem);
+
+    /// Removes a ready task, returning it if it was found.
+    fn remove_task(&mut self, task: &Self::SchedItem) -> Option<Self::SchedItem>;
+
+    /// Picks the next task to run and removes it.
+    fn pick_next_task(&mut self) -> Option<Self::SchedItem>;
+
+    /// Gives back the previously running task that is still ready.
+    /// `preempt` tells whether it was preempted rather than yielded.
+    fn put_prev_task(&mut self, prev: Self::SchedItem, preempt: bool);
+
+    /// Accounts a timer tick to the running task `current`. Returns whether
+    /// it should be preempted.
+    fn task_tick(&mut self, current: &Self::SchedItem) -> bool;
+
+    /// Returns whether no task is ready.
+    fn is_empty(&self) -> bool;
+
+    /// Sets the priority of `task`. Returns `false` if the policy has no
+    /// priorities or `prio` is out of range.
+    fn set_priority(&mut self, task: &Self::SchedItem, prio: isize) -> bool;
+}
@@ -0,0 +1,102 @@
+use crate::Scheduler;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use core::ops::Deref;
+use core::sync::atomic::{AtomicIsize, Ordering};
+
+/// A task of the [`RRScheduler`], with a time slice of `MAX_TIME_SLICE`
+/// ticks.
+pub struct RRTask<T, const MAX_TIME_SLICE: usize> {
+    inner: T,
+    time_slice: AtomicIsize,
+}
+
+impl<T, const S: usize> RRTask<T, S> {
+    pub const fn new(inner: T) -> Self {
+        Self {
+            inner,
+            time_slice: AtomicIsize::new(S as isize),
+        }
+    }
+
+    pub const fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Returns the ticks left in the time slice.
+    pub fn time_slice(&self) -> isize {
+        self.time_slice.load(Ordering::Acquire)
+    }
+
+    fn reset_time_slice(&self) {
+        self.time_slice.store(S as isize, Ordering::Release);
+    }
+}
+
+impl<T, const S: usize> Deref for RRTask<T, S> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+/// Round-robin scheduling: a task is preempted once its time slice runs
+/// out, and goes to the back of the queue with a fresh one.
+pub struct RRScheduler<T, const MAX_TIME_SLICE: usize> {
+    ready_queue: VecDeque<Arc<RRTask<T, MAX_TIME_SLICE>>>,
+}
+
+impl<T, const S: usize> RRScheduler<T, S> {
+    pub const fn new() -> Self {
+        Self {
+            ready_queue: VecDeque::new(),
+        }
+    }
+}
+
+impl<T, const S: usize> Default for RRScheduler<T, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const S: usize> Scheduler for RRScheduler<T, S> {
+    type SchedItem = Arc<RRTask<T, S>>;
+
+    fn add_task(&mut self, task: Self::SchedItem) {
+        self.ready_queue.push_back(task);
+    }
+
+    fn remove_task(&mut self, task: &Self::SchedItem) -> Option<Self::SchedItem> {
+        let idx = self.ready_queue.iter().position(|t| Arc::ptr_eq(t, task))?;
+        self.ready_queue.remove(idx)
+    }
+
+    fn pick_next_task(&mut self) -> Option<Self::SchedItem> {
+        self.ready_queue.pop_front()
+    }
+
+    fn put_prev_task(&mut self, prev: Self::SchedItem, preempt: bool) {
+        // A task preempted before its slice ran out (by a wakeup) resumes
+        // first with what is left of it.
+        if prev.time_slice() > 0 && preempt {
+            self.ready_queue.push_front(prev)
+        } else {
+            prev.reset_time_slice();
+            self.ready_queue.push_back(prev)
+        }
+    }
+
+    fn task_tick(&mut self, current: &Self::SchedItem) -> bool {
+        let old_slice = current.time_slice.fetch_sub(1, Ordering::Release);
+        old_slice <= 1
+    }
+
+    fn is_empty(&self) -> bool {
+        self.ready_queue.is_empty()
+    }
+
+    fn set_priority(&mut self, _task: &Self::SchedItem, _prio: isize) -> bool {
+        false
+    }
+}
@@ -0,0 +1,108 @@
+use crate::{CFSTask, FifoTask, RRTask, Scheduler};
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::Arc;
+
+/// Tasks that can be scheduled by the [`RtScheduler`].
+pub trait RtPriority {
+    /// Returns the real-time priority, higher runs first. `0` means the
+    /// task is not real-time.
+    fn rt_priority(&self) -> u8;
+}
+
+impl<T: RtPriority> RtPriority for FifoTask<T> {
+    fn rt_priority(&self) -> u8 {
+        self.inner().rt_priority()
+    }
+}
+
+impl<T: RtPriority, const S: usize> RtPriority for RRTask<T, S> {
+    fn rt_priority(&self) -> u8 {
+        self.inner().rt_priority()
+    }
+}
+
+impl<T: RtPriority> RtPriority for CFSTask<T> {
+    fn rt_priority(&self) -> u8 {
+        self.inner().rt_priority()
+    }
+}
+
+/// Fixed-priority real-time scheduling: the ready task with the highest
+/// priority runs, and ticks never preempt it. Tasks of equal priority run
+/// in FIFO order, and a preempted task resumes before its peers.
+///
+/// The priority of a queued task must not change; remove it first.
+pub struct RtScheduler<T> {
+    ready_queues: BTreeMap<u8, VecDeque<Arc<T>>>,
+}
+
+impl<T: RtPriority> RtScheduler<T> {
+    pub const fn new() -> Self {
+        Self {
+            ready_queues: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the priority of the highest ready task, `0` if none.
+    pub fn highest_priority(&self) -> u8 {
+        self.ready_queues
+            .last_key_value()
+            .map_or(0, |(prio, _)| *prio)
+    }
+}
+
+impl<T: RtPriority> Default for RtScheduler<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: RtPriority> Scheduler for RtScheduler<T> {
+    type SchedItem = Arc<T>;
+
+    fn add_task(&mut self, task: Self::SchedItem) {
+        let prio = task.rt_priority();
+        self.ready_queues.entry(prio).or_default().push_back(task);
+    }
+
+    fn remove_task(&mut self, task: &Self::SchedItem) -> Option<Self::SchedItem> {
+        let prio = task.rt_priority();
+        let queue = self.ready_queues.get_mut(&prio)?;
+        let idx = queue.iter().position(|t| Arc::ptr_eq(t, task))?;
+        let task = queue.remove(idx);
+        if queue.is_empty() {
+            self.ready_queues.remove(&prio);
+        }
+        task
+    }
+
+    fn pick_next_task(&mut self) -> Option<Self::SchedItem> {
+        let mut entry = self.ready_queues.last_entry()?;
+        let task = entry.get_mut().pop_front();
+        if entry.get().is_empty() {
+            entry.remove();
+        }
+        task
+    }
+
+    fn put_prev_task(&mut self, prev: Self::SchedItem, preempt: bool) {
+        let queue = self.ready_queues.entry(prev.rt_priority()).or_default();
+        if preempt {
+            queue.push_front(prev);
+        } else {
+            queue.push_back(prev);
+        }
+    }
+
+    fn task_tick(&mut self, _current: &Self::SchedItem) -> bool {
+        false
+    }
+
+    fn is_empty(&self) -> bool {
+        self.ready_queues.is_empty()
+    }
+
+    fn set_priority(&mut self, _task: &Self::SchedItem, _prio: isize) -> bool {
+        false
+    }
+}
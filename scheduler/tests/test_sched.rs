@@ -0,0 +1,154 @@
+use scheduler::*;
+use std::sync::Arc;
+
+fn pick_order<S: Scheduler>(s: &mut S) -> Vec<S::SchedItem> {
+    core::iter::from_fn(|| s.pick_next_task()).collect()
+}
+
+#[test]
+fn fifo() {
+    let mut s = FifoScheduler::new();
+    let tasks: Vec<_> = (0..4).map(|i| Arc::new(FifoTask::new(i))).collect();
+    for t in &tasks {
+        s.add_task(t.clone());
+    }
+    assert!(!s.task_tick(&tasks[0]));
+    assert!(!s.set_priority(&tasks[0], 1));
+    assert!(s.remove_task(&tasks[2]).is_some());
+    assert!(s.remove_task(&tasks[2]).is_none());
+
+    let t = s.pick_next_task().unwrap();
+    assert_eq!(**t, 0);
+    s.put_prev_task(t, true);
+    let order: Vec<_> = pick_order(&mut s).iter().map(|t| ***t).collect();
+    assert_eq!(order, [1, 3, 0]);
+}
+
+#[test]
+fn round_robin() {
+    let mut s = RRScheduler::<_, 3>::new();
+    let a = Arc::new(RRTask::new('a'));
+    let b = Arc::new(RRTask::new('b'));
+    s.add_task(a.clone());
+    s.add_task(b.clone());
+
+    let curr = s.pick_next_task().unwrap();
+    assert!(Arc::ptr_eq(&curr, &a));
+    assert!(!s.task_tick(&curr));
+    // Preempted with ticks left: it resumes first.
+    s.put_prev_task(curr, true);
+    assert_eq!(a.time_slice(), 2);
+    let curr = s.pick_next_task().unwrap();
+    assert!(Arc::ptr_eq(&curr, &a));
+    assert!(!s.task_tick(&curr));
+    assert!(s.task_tick(&curr));
+    // Out of ticks: it goes to the back with a fresh slice.
+    s.put_prev_task(curr, true);
+    assert_eq!(a.time_slice(), 3);
+    let order: Vec<_> = pick_order(&mut s).iter().map(|t| ***t).collect();
+    assert_eq!(order, ['b', 'a']);
+}
+
+#[test]
+fn cfs_fair_share() {
+    let mut s = CFScheduler::new();
+    let tasks: Vec<_> = (0..3).map(|i| Arc::new(CFSTask::new(i))).collect();
+    for t in &tasks {
+        s.add_task(t.clone());
+    }
+    let mut runs = [0; 3];
+    for _ in 0..300 {
+        let curr = s.pick_next_task().unwrap();
+        while !s.task_tick(&curr) {}
+        runs[**curr] += 1;
+        s.put_prev_task(curr, true);
+    }
+    assert_eq!(runs, [100; 3]);
+}
+
+#[test]
+fn cfs_nice() {
+    let mut s = CFScheduler::new();
+    let low = Arc::new(CFSTask::new("low"));
+    let high = Arc::new(CFSTask::new("high"));
+    assert!(s.set_priority(&low, 5));
+    assert!(!s.set_priority(&low, 20));
+    assert!(!s.set_priority(&low, -21));
+    assert_eq!(low.nice(), 5);
+    s.add_task(low.clone());
+    s.add_task(high.clone());
+
+    let (mut low_ticks, mut high_ticks) = (0u32, 0u32);
+    for _ in 0..1000 {
+        let curr = s.pick_next_task().unwrap();
+        s.task_tick(&curr);
+        if Arc::ptr_eq(&curr, &low) {
+            low_ticks += 1;
+        } else {
+            high_ticks += 1;
+        }
+        s.put_prev_task(curr, true);
+    }
+    // Nice 5 weighs 335 against 1024 for nice 0.
+    assert!((high_ticks * 335 / 1024).abs_diff(low_ticks) <= 2);
+}
+
+#[test]
+fn cfs_wakeup() {
+    let mut s = CFScheduler::new();
+    let runner = Arc::new(CFSTask::new(0));
+    let sleeper = Arc::new(CFSTask::new(1));
+    s.add_task(runner.clone());
+    let curr = s.pick_next_task().unwrap();
+    for _ in 0..100 {
+        assert!(!s.task_tick(&curr));
+    }
+    s.put_prev_task(curr, false);
+    // Picking it again raises the queue's minimum to its virtual runtime.
+    let curr = s.pick_next_task().unwrap();
+    s.add_task(sleeper.clone());
+    assert_eq!(sleeper.vruntime(), runner.vruntime());
+    s.put_prev_task(curr, false);
+
+    assert!(s.remove_task(&runner).is_some());
+    assert!(s.remove_task(&runner).is_none());
+    assert!(Arc::ptr_eq(&s.pick_next_task().unwrap(), &sleeper));
+    assert!(s.pick_next_task().is_none());
+}
+
+struct RtTask(&'static str, u8);
+
+impl RtPriority for RtTask {
+    fn rt_priority(&self) -> u8 {
+        self.1
+    }
+}
+
+#[test]
+fn real_time() {
+    let mut s = RtScheduler::new();
+    let low = Arc::new(RtTask("low", 10));
+    let high1 = Arc::new(RtTask("high1", 50));
+    let high2 = Arc::new(RtTask("high2", 50));
+    assert_eq!(s.highest_priority(), 0);
+    s.add_task(low.clone());
+    s.add_task(high1.clone());
+    s.add_task(high2.clone());
+    assert_eq!(s.highest_priority(), 50);
+
+    let curr = s.pick_next_task().unwrap();
+    assert_eq!(curr.0, "high1");
+    assert!(!s.task_tick(&curr));
+    // Preempted: it stays ahead of its peer.
+    s.put_prev_task(curr, true);
+    let curr = s.pick_next_task().unwrap();
+    assert_eq!(curr.0, "high1");
+    // Yielded: it goes behind its peer.
+    s.put_prev_task(curr, false);
+
+    assert!(s.remove_task(&low).is_some());
+    assert!(s.remove_task(&low).is_none());
+    let order: Vec<_> = pick_order(&mut s).iter().map(|t| t.0).collect();
+    assert_eq!(order, ["high2", "high1"]);
+    assert!(s.is_empty());
+}
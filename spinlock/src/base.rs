@@ -0,0 +1,238 @@
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicBool, Ordering};
+use kernel_guard::BaseGuard;
+
+#[cfg(feature = "debug")]
+use core::panic::Location;
+#[cfg(feature = "debug")]
+use core::sync::atomic::{AtomicPtr, AtomicUsize};
+
+/// Number of spins after which a waiter reports a suspected deadlock.
+#[cfg(feature = "debug")]
+const DEADLOCK_SPIN_LIMIT: usize = 100_000_000;
+
+#[cfg(feature = "debug")]
+const NO_OWNER: usize = usize::MAX;
+
+/// A test-and-test-and-set spinlock, with the guard `G` held in addition
+/// to the lock (e.g. IRQs disabled).
+pub struct BaseSpinLock<G: BaseGuard, T: ?Sized> {
+    _phantom: PhantomData<G>,
+    lock: AtomicBool,
+    #[cfg(feature = "debug")]
+    owner_cpu: AtomicUsize,
+    #[cfg(feature = "debug")]
+    owner_site: AtomicPtr<Location<'static>>,
+    data: UnsafeCell<T>,
+}
+
+/// A guard that provides mutable data access.
+///
+/// When the guard falls out of scope it will release the lock.
+pub struct BaseSpinLockGuard<'a, G: BaseGuard, T: ?Sized + 'a> {
+    lock: &'a BaseSpinLock<G, T>,
+    irq_state: G::State,
+    data: *mut T,
+}
+
+unsafe impl<G: BaseGuard, T: ?Sized + Send> Sync for BaseSpinLock<G, T> {}
+unsafe impl<G: BaseGuard, T: ?Sized + Send> Send for BaseSpinLock<G, T> {}
+
+impl<G: BaseGuard, T> BaseSpinLock<G, T> {
+    #[inline(always)]
+    pub const fn new(data: T) -> Self {
+        Self {
+            _phantom: PhantomData,
+            lock: AtomicBool::new(false),
+            #[cfg(feature = "debug")]
+            owner_cpu: AtomicUsize::new(NO_OWNER),
+            #[cfg(feature = "debug")]
+            owner_site: AtomicPtr::new(core::ptr::null_mut()),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    #[inline(always)]
+    pub fn into_inner(self) -> T {
+        let BaseSpinLock { data, .. } = self;
+        data.into_inner()
+    }
+}
+
+impl<G: BaseGuard, T: ?Sized> BaseSpinLock<G, T> {
+    /// Locks the spinlock, spinning until it is available.
+    #[inline(always)]
+    #[track_caller]
+    pub fn lock(&self) -> BaseSpinLockGuard<'_, G, T> {
+        let irq_state = G::acquire();
+        #[cfg(feature = "debug")]
+        let mut spins = 0;
+        // Can fail to lock even if the spinlock is not locked. May be more
+        // efficient than `try_lock` when called in a loop.
+        while self
+            .lock
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            // Wait until the lock looks unlocked before retrying.
+            while self.is_locked() {
+                core::hint::spin_loop();
+                #[cfg(feature = "debug")]
+                {
+                    spins += 1;
+                    if spins == DEADLOCK_SPIN_LIMIT {
+                        self.report_deadlock();
+                    }
+                }
+            }
+        }
+        #[cfg(feature = "debug")]
+        self.set_owner();
+        BaseSpinLockGuard {
+            lock: self,
+            irq_state,
+            data: self.data.get(),
+        }
+    }
+
+    /// Tries to lock the spinlock once, returning a guard if successful.
+    #[inline(always)]
+    #[track_caller]
+    pub fn try_lock(&self) -> Option<BaseSpinLockGuard<'_, G, T>> {
+        let irq_state = G::acquire();
+        if self
+            .lock
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+        {
+            #[cfg(feature = "debug")]
+            self.set_owner();
+            Some(BaseSpinLockGuard {
+                lock: self,
+                irq_state,
+                data: self.data.get(),
+            })
+        } else {
+            G::release(irq_state);
+            None
+        }
+    }
+
+    /// Returns `true` if the lock is currently held.
+    ///
+    /// The result is only a hint and may be out of date the instant it is
+    /// returned.
+    #[inline(always)]
+    pub fn is_locked(&self) -> bool {
+        self.lock.load(Ordering::Relaxed)
+    }
+
+    /// Force unlocks the spinlock.
+    ///
+    /// # Safety
+    ///
+    /// This is *extremely* unsafe if the lock is not held by the current
+    /// context. It is only intended for handing a lock over across a context
+    /// switch, where the guard is left on the stack of the previous task.
+    #[inline(always)]
+    pub unsafe fn force_unlock(&self) {
+        #[cfg(feature = "debug")]
+        self.clear_owner();
+        self.lock.store(false, Ordering::Release);
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    ///
+    /// Since this call borrows the lock mutably, no actual locking needs to
+    /// take place.
+    #[inline(always)]
+    pub fn get_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data.get() }
+    }
+}
+
+#[cfg(feature = "debug")]
+impl<G: BaseGuard, T: ?Sized> BaseSpinLock<G, T> {
+    #[inline(always)]
+    #[track_caller]
+    fn set_owner(&self) {
+        let cpu_id = crate_interface::call_interface!(crate::SpinLockIf::this_cpu_id);
+        self.owner_cpu.store(cpu_id, Ordering::Relaxed);
+        let site = Location::caller() as *const Location<'static>;
+        self.owner_site.store(site as *mut _, Ordering::Relaxed);
+    }
+
+    #[inline(always)]
+    fn clear_owner(&self) {
+        self.owner_cpu.store(NO_OWNER, Ordering::Relaxed);
+        self.owner_site
+            .store(core::ptr::null_mut(), Ordering::Relaxed);
+    }
+
+    #[cold]
+    #[track_caller]
+    fn report_deadlock(&self) -> ! {
+        let cpu_id = crate_interface::call_interface!(crate::SpinLockIf::this_cpu_id);
+        let owner_cpu = self.owner_cpu.load(Ordering::Relaxed);
+        let owner_site = self.owner_site.load(Ordering::Relaxed);
+        // The owner may still be running: leave the lock alone.
+        match unsafe { owner_site.as_ref() } {
+            Some(site) => panic!(
+                "deadlock suspected on CPU {} at {}: lock held by CPU {}{} since {}",
+                cpu_id,
+                Location::caller(),
+                owner_cpu,
+                if owner_cpu == cpu_id { " (self)" } else { "" },
+                site,
+            ),
+            None => panic!(
+                "deadlock suspected on CPU {} at {}: lock owner unknown",
+                cpu_id,
+                Location::caller(),
+            ),
+        }
+    }
+}
+
+impl<G: BaseGuard, T: Default> Default for BaseSpinLock<G, T> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new(Default::default())
+    }
+}
+
+impl<G: BaseGuard, T: ?Sized + fmt::Debug> fmt::Debug for BaseSpinLock<G, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.try_lock() {
+            Some(guard) => write!(f, "SpinLock {{ data: {:?} }}", &*guard),
+            None => write!(f, "SpinLock {{ <locked> }}"),
+        }
+    }
+}
+
+impl<G: BaseGuard, T: ?Sized> Deref for BaseSpinLockGuard<'_, G, T> {
+    type Target = T;
+    #[inline(always)]
+    fn deref(&self) -> &T {
+        unsafe { &*self.data }
+    }
+}
+
+impl<G: BaseGuard, T: ?Sized> DerefMut for BaseSpinLockGuard<'_, G, T> {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data }
+    }
+}
+
+impl<G: BaseGuard, T: ?Sized> Drop for BaseSpinLockGuard<'_, G, T> {
+    /// The dropping of the guard will release the lock it was created from.
+    #[inline(always)]
+    fn drop(&mut self) {
+        unsafe { self.lock.force_unlock() };
+        G::release(self.irq_state);
+    }
+}
@@ -1,7 +1,23 @@
 #![no_std]
 
+mod base;
+pub use base::{BaseSpinLock, BaseSpinLockGuard};
+
 mod raw;
 pub use raw::{SpinRaw, SpinRawGuard};
 
 mod noirq;
 pub use noirq::{SpinNoIrq, SpinNoIrqGuard};
+
+/// Spinlock interface, used by the `debug` feature to record lock owners.
+///
+/// This trait is defined with the [`#[def_interface]`][1] attribute. Users
+/// should implement it with [`#[impl_interface]`][2] in any other crate.
+///
+/// [1]: crate_interface::def_interface
+/// [2]: crate_interface::impl_interface
+#[crate_interface::def_interface]
+pub trait SpinLockIf {
+    /// Returns the ID of the current CPU.
+    fn this_cpu_id() -> usize;
+}
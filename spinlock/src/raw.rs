@@ -1,55 +1,11 @@
-use core::cell::UnsafeCell;
-use core::ops::{Deref, DerefMut};
+use crate::{BaseSpinLock, BaseSpinLockGuard};
+use kernel_guard::NoOp;
 
-pub struct SpinRaw<T> {
-    data: UnsafeCell<T>,
-}
+/// A raw spinlock that does nothing but spin.
+///
+/// The caller must disable IRQs (and preemption) by itself if the lock may
+/// also be taken in an IRQ handler.
+pub type SpinRaw<T> = BaseSpinLock<NoOp, T>;
 
-pub struct SpinRawGuard<T> {
-    data: *mut T,
-}
-
-unsafe impl<T> Sync for SpinRaw<T> {}
-unsafe impl<T> Send for SpinRaw<T> {}
-
-impl<T> SpinRaw<T> {
-    #[inline(always)]
-    pub const fn new(data: T) -> Self {
-        Self {
-            data: UnsafeCell::new(data),
-        }
-    }
-
-    #[inline(always)]
-    pub fn into_inner(self) -> T {
-        let SpinRaw { data, .. } = self;
-        data.into_inner()
-    }
-
-    #[inline(always)]
-    pub fn lock(&self) -> SpinRawGuard<T> {
-        SpinRawGuard {
-            data: unsafe { &mut *self.data.get() },
-        }
-    }
-}
-
-impl<T> Deref for SpinRawGuard<T> {
-    type Target = T;
-    #[inline(always)]
-    fn deref(&self) -> &T {
-        unsafe { &*self.data }
-    }
-}
-
-impl<T> DerefMut for SpinRawGuard<T> {
-    #[inline(always)]
-    fn deref_mut(&mut self) -> &mut T {
-        unsafe { &mut *self.data }
-    }
-}
-
-impl<T> Drop for SpinRawGuard<T> {
-    #[inline(always)]
-    fn drop(&mut self) {}
-}
+/// A guard that provides mutable data access for [`SpinRaw`].
+pub type SpinRawGuard<'a, T> = BaseSpinLockGuard<'a, NoOp, T>;
@@ -19,8 +19,66 @@ impl Inner {
 
 static SPIN: SpinRaw<Inner> = SpinRaw::new(Inner::new());
 
+#[cfg(feature = "debug")]
+struct SpinLockIfImpl;
+
+#[cfg(feature = "debug")]
+#[crate_interface::impl_interface]
+impl spinlock::SpinLockIf for SpinLockIfImpl {
+    fn this_cpu_id() -> usize {
+        0
+    }
+}
+
 #[test]
 fn test_lock() {
     SPIN.lock().set(1);
     assert_eq!(SPIN.lock().get(), 1);
 }
+
+#[test]
+fn test_try_lock() {
+    let spin = SpinRaw::new(0);
+    let guard = spin.try_lock().unwrap();
+    assert!(spin.is_locked());
+    assert!(spin.try_lock().is_none());
+    drop(guard);
+    assert!(!spin.is_locked());
+    assert!(spin.try_lock().is_some());
+}
+
+#[test]
+fn test_force_unlock() {
+    let spin = SpinRaw::new(0);
+    core::mem::forget(spin.lock());
+    assert!(spin.is_locked());
+    unsafe { spin.force_unlock() };
+    assert!(!spin.is_locked());
+    *spin.lock() += 1;
+    assert_eq!(spin.into_inner(), 1);
+}
+
+#[test]
+fn test_contention() {
+    use std::sync::Arc;
+    use std::thread;
+
+    const THREADS: usize = 4;
+    const ROUNDS: usize = 10000;
+
+    let spin = Arc::new(SpinRaw::new(0usize));
+    let handles: Vec<_> = (0..THREADS)
+        .map(|_| {
+            let spin = spin.clone();
+            thread::spawn(move || {
+                for _ in 0..ROUNDS {
+                    *spin.lock() += 1;
+                }
+            })
+        })
+        .collect();
+    for h in handles {
+        h.join().unwrap();
+    }
+    assert_eq!(*spin.lock(), THREADS * ROUNDS);
+}